@@ -0,0 +1,24 @@
+use std::{env, path::PathBuf};
+
+/// Set to also write the generated header over the committed `include/ra2_mix.h`
+const UPDATE_HEADER: &str = "RA2_MIX_FFI_UPDATE_HEADER";
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed={}", UPDATE_HEADER);
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("invalid cbindgen.toml");
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            // The source tree is only written on request, `tests/main.rs` checks the committed header is current
+            bindings.write_to_file(out_dir.join("ra2_mix.h"));
+            if env::var_os(UPDATE_HEADER).is_some() {
+                bindings.write_to_file(crate_dir.join("include/ra2_mix.h"));
+            }
+        }
+        // Keep the committed header when the sources do not parse, the compiler reports the error
+        Err(e) => println!("cargo:warning=Failed to generate ra2_mix.h: {}", e),
+    }
+}
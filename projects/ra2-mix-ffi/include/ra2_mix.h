@@ -0,0 +1,185 @@
+#ifndef RA2_MIX_H
+#define RA2_MIX_H
+
+/* Generated by cbindgen from projects/ra2-mix-ffi, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call, `RA2_MIX_STATUS_OK` on success
+//
+// The codes after `RA2_MIX_STATUS_OK` mirror the kinds of `MixError`, [`ra2_mix_last_error`] gives the message.
+typedef enum Ra2MixStatus {
+  // The call succeeded
+  RA2_MIX_STATUS_OK = 0,
+  // Reading or writing a file failed
+  RA2_MIX_STATUS_IO_ERROR = 1,
+  // The data ends before a structure
+  RA2_MIX_STATUS_TRUNCATED = 2,
+  // An index entry points outside the archive body
+  RA2_MIX_STATUS_INVALID_INDEX = 3,
+  // Decrypting an encrypted header failed
+  RA2_MIX_STATUS_CRYPTO = 4,
+  // The data is not a valid archive
+  RA2_MIX_STATUS_INVALID_FORMAT = 5,
+  // A file or folder does not exist
+  RA2_MIX_STATUS_FILE_NOT_FOUND = 6,
+  // Two file names hash to the same ID
+  RA2_MIX_STATUS_NAME_COLLISION = 7,
+  // An entry has different contents in merged archives
+  RA2_MIX_STATUS_ENTRY_CONFLICT = 8,
+  // The archive holds more files than the header can describe
+  RA2_MIX_STATUS_TOO_MANY_FILES = 9,
+  // The archive body is larger than the header can describe
+  RA2_MIX_STATUS_BODY_TOO_LARGE = 10,
+  // The operation was cancelled
+  RA2_MIX_STATUS_CANCELLED = 11,
+  // The archive uses a feature the library cannot handle
+  RA2_MIX_STATUS_UNSUPPORTED = 12,
+  // An argument is `NULL`, out of range or not UTF-8
+  RA2_MIX_STATUS_INVALID_ARGUMENT = 13,
+  // The buffer cannot hold the data, the required size was written
+  RA2_MIX_STATUS_BUFFER_TOO_SMALL = 14,
+  // The library panicked, which is a bug, only returned by builds that unwind on panic
+  RA2_MIX_STATUS_PANIC = 15,
+  // The archive has no entry with the file ID
+  RA2_MIX_STATUS_ENTRY_NOT_FOUND = 16,
+} Ra2MixStatus;
+
+// An archive held in memory, created by [`ra2_mix_new`], [`ra2_mix_load`] or [`ra2_mix_decode`] and released by
+// [`ra2_mix_free`]
+typedef struct Ra2MixPackage Ra2MixPackage;
+
+// An entry of an archive
+typedef struct Ra2MixEntry {
+  // The file ID
+  int32_t id;
+  // The file name, or `NULL` if unknown, valid until the archive is changed or freed
+  const char *name;
+  // The size of the data in bytes
+  uint64_t size;
+} Ra2MixEntry;
+
+// Bytes allocated by the library, released by [`ra2_mix_buffer_free`]
+typedef struct Ra2MixBuffer {
+  // The bytes, or `NULL` if empty
+  uint8_t *data;
+  // The number of bytes
+  size_t len;
+} Ra2MixBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The version of the library, such as `0.0.1`
+const char *ra2_mix_version(void);
+
+// The file ID of a name, as used by the index of an archive, or `0` if `name` is `NULL` or not UTF-8
+//
+// # Safety
+// `name` must be `NULL` or a NUL terminated string.
+int32_t ra2_mix_id(const char *name);
+
+// Creates an empty archive
+//
+// # Arguments
+// * `game` - The game of the archive, numbered as in the XCC utilities, `5` for Red Alert 2 and `6` for Yuri's Revenge
+// * `out` - Receives the archive
+//
+// # Safety
+// `out` must point to writable memory for a pointer.
+enum Ra2MixStatus ra2_mix_new(uint32_t game,
+                              struct Ra2MixPackage **out);
+
+// Reads an archive from disk
+//
+// # Safety
+// `path` must be a NUL terminated UTF-8 string and `out` must point to writable memory for a pointer.
+enum Ra2MixStatus ra2_mix_load(const char *path,
+                               struct Ra2MixPackage **out);
+
+// Decodes an archive from memory
+//
+// # Safety
+// `data` must point to `len` readable bytes and `out` must point to writable memory for a pointer.
+enum Ra2MixStatus ra2_mix_decode(const uint8_t *data, size_t len, struct Ra2MixPackage **out);
+
+// Releases an archive, doing nothing if `mix` is `NULL`
+//
+// # Safety
+// `mix` must be `NULL` or an archive that was not freed yet.
+void ra2_mix_free(struct Ra2MixPackage *mix);
+
+// The number of entries in the archive, `0` if `mix` is `NULL`
+//
+// # Safety
+// `mix` must be `NULL` or a live archive.
+size_t ra2_mix_entry_count(const struct Ra2MixPackage *mix);
+
+// Describes the entry at a position, entries are sorted by ID
+//
+// # Safety
+// `mix` must be a live archive and `out` must point to a writable [`Ra2MixEntry`].
+enum Ra2MixStatus ra2_mix_entry(struct Ra2MixPackage *mix, size_t index, struct Ra2MixEntry *out);
+
+// Copies the data of an entry into a buffer
+//
+// `size` receives the size of the entry. If the buffer is too small, nothing is copied and
+// `RA2_MIX_STATUS_BUFFER_TOO_SMALL` is returned, so passing a `NULL` buffer asks for the size.
+//
+// # Safety
+// `mix` must be a live archive, `buffer` must be `NULL` or point to `capacity` writable bytes and `size` must be
+// `NULL` or point to a writable `size_t`.
+enum Ra2MixStatus ra2_mix_read(struct Ra2MixPackage *mix,
+                               int32_t id,
+                               uint8_t *buffer,
+                               size_t capacity,
+                               size_t *size);
+
+// Adds an entry, replacing any entry with the same ID
+//
+// # Safety
+// `mix` must be a live archive, `name` a NUL terminated UTF-8 string and `data` must point to `len` readable bytes.
+enum Ra2MixStatus ra2_mix_add(struct Ra2MixPackage *mix,
+                              const char *name,
+                              const uint8_t *data,
+                              size_t len);
+
+// Removes the entry with an ID
+//
+// # Safety
+// `mix` must be a live archive.
+enum Ra2MixStatus ra2_mix_remove(struct Ra2MixPackage *mix, int32_t id);
+
+// Encodes the archive into a buffer owned by the caller
+//
+// # Safety
+// `mix` must be a live archive and `out` must point to a writable [`Ra2MixBuffer`].
+enum Ra2MixStatus ra2_mix_encode(struct Ra2MixPackage *mix, struct Ra2MixBuffer *out);
+
+// Releases a buffer returned by [`ra2_mix_encode`]
+//
+// # Safety
+// `buffer` must come from [`ra2_mix_encode`] and not be freed yet.
+void ra2_mix_buffer_free(struct Ra2MixBuffer buffer);
+
+// Encodes the archive and writes it to disk
+//
+// # Safety
+// `mix` must be a live archive and `path` a NUL terminated UTF-8 string.
+enum Ra2MixStatus ra2_mix_save(struct Ra2MixPackage *mix, const char *path);
+
+// The message of the last failed call on this thread, or `NULL` if the last call succeeded
+//
+// The string stays valid until the next call on this thread that returns a status.
+const char *ra2_mix_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RA2_MIX_H */
@@ -0,0 +1,128 @@
+//! Status codes and the last error message of the calling thread
+
+use ra2_mix::MixError;
+use std::{
+    cell::RefCell,
+    ffi::{CString, c_char},
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+/// The result of a call, `RA2_MIX_STATUS_OK` on success
+///
+/// The codes after `RA2_MIX_STATUS_OK` mirror the kinds of `MixError`, [`ra2_mix_last_error`] gives the message.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ra2MixStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// Reading or writing a file failed
+    IoError = 1,
+    /// The data ends before a structure
+    Truncated = 2,
+    /// An index entry points outside the archive body
+    InvalidIndex = 3,
+    /// Decrypting an encrypted header failed
+    Crypto = 4,
+    /// The data is not a valid archive
+    InvalidFormat = 5,
+    /// A file or folder does not exist
+    FileNotFound = 6,
+    /// Two file names hash to the same ID
+    NameCollision = 7,
+    /// An entry has different contents in merged archives
+    EntryConflict = 8,
+    /// The archive holds more files than the header can describe
+    TooManyFiles = 9,
+    /// The archive body is larger than the header can describe
+    BodyTooLarge = 10,
+    /// The operation was cancelled
+    Cancelled = 11,
+    /// The archive uses a feature the library cannot handle
+    Unsupported = 12,
+    /// An argument is `NULL`, out of range or not UTF-8
+    InvalidArgument = 13,
+    /// The buffer cannot hold the data, the required size was written
+    BufferTooSmall = 14,
+    /// The library panicked, which is a bug, only returned by builds that unwind on panic
+    Panic = 15,
+    /// The archive has no entry with the file ID
+    EntryNotFound = 16,
+}
+
+impl From<&MixError> for Ra2MixStatus {
+    fn from(error: &MixError) -> Self {
+        match error.root() {
+            MixError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound => Ra2MixStatus::FileNotFound,
+            MixError::IoError(_) => Ra2MixStatus::IoError,
+            MixError::Truncated { .. } => Ra2MixStatus::Truncated,
+            MixError::InvalidIndex { .. } => Ra2MixStatus::InvalidIndex,
+            MixError::Crypto { .. } => Ra2MixStatus::Crypto,
+            MixError::InvalidFormat(_) => Ra2MixStatus::InvalidFormat,
+            MixError::FileNotFound(_) => Ra2MixStatus::FileNotFound,
+            MixError::EntryNotFound(_) => Ra2MixStatus::EntryNotFound,
+            MixError::NameCollision { .. } => Ra2MixStatus::NameCollision,
+            MixError::EntryConflict { .. } => Ra2MixStatus::EntryConflict,
+            MixError::TooManyFiles { .. } => Ra2MixStatus::TooManyFiles,
+            MixError::BodyTooLarge { .. } => Ra2MixStatus::BodyTooLarge,
+            MixError::Cancelled => Ra2MixStatus::Cancelled,
+            MixError::Unsupported(_) => Ra2MixStatus::Unsupported,
+            MixError::Context { .. } => unreachable!("the root error has no context"),
+        }
+    }
+}
+
+/// Why a call failed
+pub(crate) enum Failure {
+    /// The library returned an error
+    Mix(MixError),
+    /// An argument is invalid
+    InvalidArgument(String),
+    /// The output buffer is too small
+    BufferTooSmall { needed: usize, capacity: usize },
+}
+
+impl From<MixError> for Failure {
+    fn from(error: MixError) -> Self {
+        Failure::Mix(error)
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs the body of an exported function, turning errors and panics into status codes and recording the message
+///
+/// Panics are only caught when the library is built with `panic = "unwind"`. The workspace release profile aborts
+/// on panic, which ends the process before `catch_unwind` can return.
+pub(crate) fn guard(body: impl FnOnce() -> Result<(), Failure>) -> Ra2MixStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => (Ra2MixStatus::Ok, None),
+        Ok(Err(Failure::Mix(e))) => (Ra2MixStatus::from(&e), Some(e.to_string())),
+        Ok(Err(Failure::InvalidArgument(message))) => (Ra2MixStatus::InvalidArgument, Some(message)),
+        Ok(Err(Failure::BufferTooSmall { needed, capacity })) => {
+            let message = format!("The entry needs {} bytes, the buffer holds {}", needed, capacity);
+            (Ra2MixStatus::BufferTooSmall, Some(message))
+        }
+        Err(_) => (Ra2MixStatus::Panic, Some("The library panicked".to_string())),
+    };
+    match message {
+        Some(message) => set_last_error(message),
+        None => LAST_ERROR.with(|last| *last.borrow_mut() = None),
+    }
+    status
+}
+
+/// The message of the last failed call on this thread, or `NULL` if the last call succeeded
+///
+/// The string stays valid until the next call on this thread that returns a status.
+#[no_mangle]
+pub extern "C" fn ra2_mix_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
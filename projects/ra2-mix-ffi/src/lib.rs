@@ -0,0 +1,296 @@
+#![deny(missing_debug_implementations, missing_copy_implementations)]
+#![warn(missing_docs)]
+#![doc = include_str!("../readme.md")]
+
+mod errors;
+
+pub use crate::errors::{Ra2MixStatus, ra2_mix_last_error};
+use crate::errors::{Failure, guard};
+use ra2_mix::{ArchiveSource, MixError, MixPackage, XccGame, ra2_crc};
+use std::{
+    ffi::{CStr, CString, c_char},
+    path::Path,
+    ptr, slice,
+};
+
+/// An archive held in memory, created by [`ra2_mix_new`], [`ra2_mix_load`] or [`ra2_mix_decode`] and released by
+/// [`ra2_mix_free`]
+#[derive(Debug)]
+pub struct Ra2MixPackage {
+    package: MixPackage,
+    /// The entries sorted by ID, with the names handed out to callers
+    entries: Vec<(i32, Option<CString>, u64)>,
+}
+
+impl Ra2MixPackage {
+    fn new(package: MixPackage) -> Self {
+        let mut handle = Self { package, entries: Vec::new() };
+        handle.refresh();
+        handle
+    }
+    /// Lists the entries again after a change, which invalidates the names given out before
+    fn refresh(&mut self) {
+        let mut entries: Vec<_> = self
+            .package
+            .list()
+            .into_iter()
+            .map(|entry| (entry.id, entry.name.and_then(|name| CString::new(name).ok()), entry.size))
+            .collect();
+        entries.sort_by_key(|entry| entry.0);
+        self.entries = entries;
+    }
+}
+
+/// An entry of an archive
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Ra2MixEntry {
+    /// The file ID
+    pub id: i32,
+    /// The file name, or `NULL` if unknown, valid until the archive is changed or freed
+    pub name: *const c_char,
+    /// The size of the data in bytes
+    pub size: u64,
+}
+
+/// Bytes allocated by the library, released by [`ra2_mix_buffer_free`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Ra2MixBuffer {
+    /// The bytes, or `NULL` if empty
+    pub data: *mut u8,
+    /// The number of bytes
+    pub len: usize,
+}
+
+/// Reads a string argument
+unsafe fn string_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if value.is_null() {
+        return Err(Failure::InvalidArgument(format!("`{}` is NULL", name)));
+    }
+    CStr::from_ptr(value).to_str().map_err(|_| Failure::InvalidArgument(format!("`{}` is not UTF-8", name)))
+}
+
+/// Reads a byte array argument, which may be `NULL` when empty
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize, name: &str) -> Result<&'a [u8], Failure> {
+    match data.is_null() {
+        true if len == 0 => Ok(&[]),
+        true => Err(Failure::InvalidArgument(format!("`{}` is NULL", name))),
+        false => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Borrows the archive behind a handle
+unsafe fn handle_arg<'a>(handle: *mut Ra2MixPackage) -> Result<&'a mut Ra2MixPackage, Failure> {
+    handle.as_mut().ok_or_else(|| Failure::InvalidArgument("`mix` is NULL".to_string()))
+}
+
+/// Hands a new archive to the caller
+unsafe fn return_handle(out: *mut *mut Ra2MixPackage, package: MixPackage) -> Result<(), Failure> {
+    if out.is_null() {
+        return Err(Failure::InvalidArgument("`out` is NULL".to_string()));
+    }
+    *out = Box::into_raw(Box::new(Ra2MixPackage::new(package)));
+    Ok(())
+}
+
+/// The version of the library, such as `0.0.1`
+#[no_mangle]
+pub extern "C" fn ra2_mix_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// The file ID of a name, as used by the index of an archive, or `0` if `name` is `NULL` or not UTF-8
+///
+/// # Safety
+/// `name` must be `NULL` or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_id(name: *const c_char) -> i32 {
+    string_arg(name, "name").map_or(0, ra2_crc)
+}
+
+/// Creates an empty archive
+///
+/// # Arguments
+/// * `game` - The game of the archive, numbered as in the XCC utilities, `5` for Red Alert 2 and `6` for Yuri's Revenge
+/// * `out` - Receives the archive
+///
+/// # Safety
+/// `out` must point to writable memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_new(game: u32, out: *mut *mut Ra2MixPackage) -> Ra2MixStatus {
+    guard(|| return_handle(out, MixPackage { game: XccGame::from(game), ..Default::default() }))
+}
+
+/// Reads an archive from disk
+///
+/// # Safety
+/// `path` must be a NUL terminated UTF-8 string and `out` must point to writable memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_load(path: *const c_char, out: *mut *mut Ra2MixPackage) -> Ra2MixStatus {
+    guard(|| {
+        let package = MixPackage::load(Path::new(string_arg(path, "path")?))?;
+        return_handle(out, package)
+    })
+}
+
+/// Decodes an archive from memory
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `out` must point to writable memory for a pointer.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_decode(data: *const u8, len: usize, out: *mut *mut Ra2MixPackage) -> Ra2MixStatus {
+    guard(|| {
+        let package = MixPackage::decode(bytes_arg(data, len, "data")?)?;
+        return_handle(out, package)
+    })
+}
+
+/// Releases an archive, doing nothing if `mix` is `NULL`
+///
+/// # Safety
+/// `mix` must be `NULL` or an archive that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_free(mix: *mut Ra2MixPackage) {
+    if !mix.is_null() {
+        drop(Box::from_raw(mix));
+    }
+}
+
+/// The number of entries in the archive, `0` if `mix` is `NULL`
+///
+/// # Safety
+/// `mix` must be `NULL` or a live archive.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_entry_count(mix: *const Ra2MixPackage) -> usize {
+    mix.as_ref().map_or(0, |handle| handle.entries.len())
+}
+
+/// Describes the entry at a position, entries are sorted by ID
+///
+/// # Safety
+/// `mix` must be a live archive and `out` must point to a writable [`Ra2MixEntry`].
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_entry(mix: *mut Ra2MixPackage, index: usize, out: *mut Ra2MixEntry) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        let (id, name, size) = match handle.entries.get(index) {
+            Some(entry) => entry,
+            None => return Err(Failure::InvalidArgument(format!("No entry at position {}", index))),
+        };
+        let out = out.as_mut().ok_or_else(|| Failure::InvalidArgument("`out` is NULL".to_string()))?;
+        *out = Ra2MixEntry { id: *id, name: name.as_ref().map_or(ptr::null(), |name| name.as_ptr()), size: *size };
+        Ok(())
+    })
+}
+
+/// Copies the data of an entry into a buffer
+///
+/// `size` receives the size of the entry. If the buffer is too small, nothing is copied and
+/// `RA2_MIX_STATUS_BUFFER_TOO_SMALL` is returned, so passing a `NULL` buffer asks for the size.
+///
+/// # Safety
+/// `mix` must be a live archive, `buffer` must be `NULL` or point to `capacity` writable bytes and `size` must be
+/// `NULL` or point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_read(
+    mix: *mut Ra2MixPackage,
+    id: i32,
+    buffer: *mut u8,
+    capacity: usize,
+    size: *mut usize,
+) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        let data = match handle.package.get_by_id(id) {
+            Some(data) => data,
+            None => return Err(Failure::Mix(MixError::EntryNotFound(id))),
+        };
+        if let Some(size) = size.as_mut() {
+            *size = data.len();
+        }
+        if buffer.is_null() || capacity < data.len() {
+            return Err(Failure::BufferTooSmall { needed: data.len(), capacity });
+        }
+        ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+        Ok(())
+    })
+}
+
+/// Adds an entry, replacing any entry with the same ID
+///
+/// # Safety
+/// `mix` must be a live archive, `name` a NUL terminated UTF-8 string and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_add(
+    mix: *mut Ra2MixPackage,
+    name: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        let name = string_arg(name, "name")?;
+        let data = bytes_arg(data, len, "data")?;
+        handle.package.insert(name.to_string(), data.to_vec());
+        handle.refresh();
+        Ok(())
+    })
+}
+
+/// Removes the entry with an ID
+///
+/// # Safety
+/// `mix` must be a live archive.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_remove(mix: *mut Ra2MixPackage, id: i32) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        if handle.package.remove_by_id(id).is_none() {
+            return Err(Failure::Mix(MixError::EntryNotFound(id)));
+        }
+        handle.refresh();
+        Ok(())
+    })
+}
+
+/// Encodes the archive into a buffer owned by the caller
+///
+/// # Safety
+/// `mix` must be a live archive and `out` must point to a writable [`Ra2MixBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_encode(mix: *mut Ra2MixPackage, out: *mut Ra2MixBuffer) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        let out = out.as_mut().ok_or_else(|| Failure::InvalidArgument("`out` is NULL".to_string()))?;
+        let data = handle.package.clone().encode()?.into_boxed_slice();
+        let len = data.len();
+        *out = Ra2MixBuffer { data: Box::into_raw(data).cast(), len };
+        Ok(())
+    })
+}
+
+/// Releases a buffer returned by [`ra2_mix_encode`]
+///
+/// # Safety
+/// `buffer` must come from [`ra2_mix_encode`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_buffer_free(buffer: Ra2MixBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Encodes the archive and writes it to disk
+///
+/// # Safety
+/// `mix` must be a live archive and `path` a NUL terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn ra2_mix_save(mix: *mut Ra2MixPackage, path: *const c_char) -> Ra2MixStatus {
+    guard(|| {
+        let handle = handle_arg(mix)?;
+        let path = string_arg(path, "path")?;
+        handle.package.clone().save(Path::new(path))?;
+        Ok(())
+    })
+}
@@ -0,0 +1,77 @@
+/* Builds, saves, reloads and edits an archive through the C API, exits with 0 on success */
+
+#include "ra2_mix.h"
+
+#include <stdio.h>
+#include <string.h>
+
+#define CHECK(call)                                                                                                    \
+    do {                                                                                                               \
+        Ra2MixStatus status = (call);                                                                                  \
+        if (status != RA2_MIX_STATUS_OK) {                                                                             \
+            fprintf(stderr, "%s:%d: %s failed with %d: %s\n", __FILE__, __LINE__, #call, status, ra2_mix_last_error()); \
+            return 1;                                                                                                  \
+        }                                                                                                              \
+    } while (0)
+
+#define EXPECT(condition)                                                                                              \
+    do {                                                                                                               \
+        if (!(condition)) {                                                                                            \
+            fprintf(stderr, "%s:%d: expected %s\n", __FILE__, __LINE__, #condition);                                   \
+            return 1;                                                                                                  \
+        }                                                                                                              \
+    } while (0)
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <archive path>\n", argv[0]);
+        return 2;
+    }
+    const char *rules = "[General]\nName=Test\n";
+    const char *art = "[Art]\n";
+
+    Ra2MixPackage *mix = NULL;
+    CHECK(ra2_mix_new(6, &mix));
+    CHECK(ra2_mix_add(mix, "rulesmd.ini", (const uint8_t *)rules, strlen(rules)));
+    CHECK(ra2_mix_add(mix, "artmd.ini", (const uint8_t *)art, strlen(art)));
+    EXPECT(ra2_mix_entry_count(mix) == 2);
+    CHECK(ra2_mix_save(mix, argv[1]));
+    ra2_mix_free(mix);
+
+    mix = NULL;
+    CHECK(ra2_mix_load(argv[1], &mix));
+    EXPECT(ra2_mix_entry_count(mix) == 2);
+    for (size_t i = 0; i < ra2_mix_entry_count(mix); i++) {
+        Ra2MixEntry entry;
+        CHECK(ra2_mix_entry(mix, i, &entry));
+        EXPECT(entry.name != NULL);
+        EXPECT(entry.id == ra2_mix_id(entry.name));
+    }
+
+    size_t size = 0;
+    EXPECT(ra2_mix_read(mix, ra2_mix_id("rulesmd.ini"), NULL, 0, &size) == RA2_MIX_STATUS_BUFFER_TOO_SMALL);
+    EXPECT(size == strlen(rules));
+    uint8_t buffer[64];
+    CHECK(ra2_mix_read(mix, ra2_mix_id("rulesmd.ini"), buffer, sizeof(buffer), &size));
+    EXPECT(size == strlen(rules) && memcmp(buffer, rules, size) == 0);
+
+    CHECK(ra2_mix_remove(mix, ra2_mix_id("artmd.ini")));
+    EXPECT(ra2_mix_remove(mix, ra2_mix_id("artmd.ini")) == RA2_MIX_STATUS_ENTRY_NOT_FOUND);
+    EXPECT(ra2_mix_last_error() != NULL);
+
+    Ra2MixBuffer encoded;
+    CHECK(ra2_mix_encode(mix, &encoded));
+    ra2_mix_free(mix);
+    mix = NULL;
+    CHECK(ra2_mix_decode(encoded.data, encoded.len, &mix));
+    ra2_mix_buffer_free(encoded);
+    EXPECT(ra2_mix_entry_count(mix) == 1);
+    ra2_mix_free(mix);
+
+    const uint8_t garbage[] = {1, 2, 3};
+    EXPECT(ra2_mix_decode(garbage, sizeof(garbage), &mix) != RA2_MIX_STATUS_OK);
+    EXPECT(ra2_mix_load(NULL, &mix) == RA2_MIX_STATUS_INVALID_ARGUMENT);
+
+    printf("ok %s\n", ra2_mix_version());
+    return 0;
+}
@@ -0,0 +1,76 @@
+use ra2_mix_ffi::*;
+use std::{ffi::CStr, path::PathBuf, process::Command, ptr};
+
+#[test]
+fn round_trip() {
+    unsafe {
+        let mut mix = ptr::null_mut();
+        assert_eq!(ra2_mix_new(6, &mut mix), Ra2MixStatus::Ok);
+        let data = b"[General]";
+        assert_eq!(ra2_mix_add(mix, c"rulesmd.ini".as_ptr(), data.as_ptr(), data.len()), Ra2MixStatus::Ok);
+        assert_eq!(ra2_mix_entry_count(mix), 1);
+
+        let mut encoded = Ra2MixBuffer { data: ptr::null_mut(), len: 0 };
+        assert_eq!(ra2_mix_encode(mix, &mut encoded), Ra2MixStatus::Ok);
+        ra2_mix_free(mix);
+        let mut decoded = ptr::null_mut();
+        assert_eq!(ra2_mix_decode(encoded.data, encoded.len, &mut decoded), Ra2MixStatus::Ok);
+        ra2_mix_buffer_free(encoded);
+
+        let mut entry = Ra2MixEntry { id: 0, name: ptr::null(), size: 0 };
+        assert_eq!(ra2_mix_entry(decoded, 0, &mut entry), Ra2MixStatus::Ok);
+        assert_eq!(CStr::from_ptr(entry.name).to_str(), Ok("rulesmd.ini"));
+        assert_eq!(entry.id, ra2_mix_id(c"RULESMD.INI".as_ptr()));
+
+        let mut buffer = [0u8; 4];
+        let mut size = 0;
+        let status = ra2_mix_read(decoded, entry.id, buffer.as_mut_ptr(), buffer.len(), &mut size);
+        assert_eq!(status, Ra2MixStatus::BufferTooSmall);
+        assert_eq!(size, data.len());
+        assert!(!ra2_mix_last_error().is_null());
+
+        assert_eq!(ra2_mix_remove(decoded, 1234), Ra2MixStatus::EntryNotFound);
+        assert_eq!(ra2_mix_entry(decoded, 5, &mut entry), Ra2MixStatus::InvalidArgument);
+        assert_eq!(ra2_mix_load(c"missing.mix".as_ptr(), &mut mix), Ra2MixStatus::FileNotFound);
+        ra2_mix_free(decoded);
+    }
+}
+
+/// Checks the committed header matches the sources, regenerate it with `RA2_MIX_FFI_UPDATE_HEADER=1 cargo build`
+#[test]
+fn header_is_current() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/ra2_mix.h"));
+    let committed = include_str!("../include/ra2_mix.h");
+    assert!(generated == committed, "include/ra2_mix.h is out of date, rebuild with RA2_MIX_FFI_UPDATE_HEADER=1");
+}
+
+/// Compiles `tests/c/smoke.c` against the static library and runs it
+#[test]
+#[cfg(unix)]
+fn c_program() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The test binary is in `target/<profile>/deps`, next to the libraries of the crate
+    let deps = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let library = [deps.join("libra2_mix_ffi.a"), deps.parent().unwrap().join("libra2_mix_ffi.a")]
+        .into_iter()
+        .find(|path| path.exists())
+        .expect("the static library is built with the tests");
+
+    let folder = tempfile::tempdir().unwrap();
+    let program = folder.path().join("smoke");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .arg(root.join("tests/c/smoke.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()
+        .expect("a C compiler is installed");
+    assert!(status.success());
+
+    let output = Command::new(&program).arg(folder.path().join("test.mix")).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("ok "));
+}
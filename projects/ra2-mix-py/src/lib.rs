@@ -0,0 +1,204 @@
+#![deny(missing_debug_implementations, missing_copy_implementations)]
+#![warn(missing_docs)]
+#![doc = include_str!("../readme.md")]
+
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyFileNotFoundError, PyKeyError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+use ra2_mix::{ArchiveSource, XccGame, ra2_crc};
+use std::path::PathBuf;
+
+create_exception!(ra2_mix, MixError, PyException, "Base class of the errors raised when reading or writing archives");
+create_exception!(ra2_mix, TruncatedError, MixError, "The data ends before a structure of the archive");
+create_exception!(ra2_mix, InvalidIndexError, MixError, "An index entry points outside the archive body");
+create_exception!(ra2_mix, CryptoError, MixError, "Decrypting an encrypted header failed");
+create_exception!(ra2_mix, InvalidFormatError, MixError, "The data is not a valid archive");
+create_exception!(ra2_mix, NameCollisionError, MixError, "Two file names hash to the same file ID");
+create_exception!(ra2_mix, EntryConflictError, MixError, "An entry has different contents in merged archives");
+create_exception!(ra2_mix, TooManyFilesError, MixError, "The archive holds more files than the header can describe");
+create_exception!(ra2_mix, BodyTooLargeError, MixError, "The archive body is larger than the header can describe");
+create_exception!(ra2_mix, UnsupportedError, MixError, "The archive uses a feature the library cannot handle");
+
+/// Converts a library error into the matching Python exception
+fn to_py_err(error: ra2_mix::MixError) -> PyErr {
+    use ra2_mix::MixError as E;
+    let message = error.to_string();
+    match error.root() {
+        // `OSError` picks the subclass from the error kind, such as `FileNotFoundError`
+        E::IoError(e) => PyErr::from(std::io::Error::new(e.kind(), message)),
+        E::FileNotFound(_) => PyFileNotFoundError::new_err(message),
+        E::EntryNotFound(_) => PyKeyError::new_err(message),
+        E::Truncated { .. } => TruncatedError::new_err(message),
+        E::InvalidIndex { .. } => InvalidIndexError::new_err(message),
+        E::Crypto { .. } => CryptoError::new_err(message),
+        E::InvalidFormat(_) => InvalidFormatError::new_err(message),
+        E::NameCollision { .. } => NameCollisionError::new_err(message),
+        E::EntryConflict { .. } => EntryConflictError::new_err(message),
+        E::TooManyFiles { .. } => TooManyFilesError::new_err(message),
+        E::BodyTooLarge { .. } => BodyTooLargeError::new_err(message),
+        E::Unsupported(_) => UnsupportedError::new_err(message),
+        E::Cancelled | E::Context { .. } => MixError::new_err(message),
+    }
+}
+
+/// An entry given by file name or by file ID
+#[derive(Debug, FromPyObject)]
+enum EntryKey {
+    /// A file ID, signed as returned by `ra2_crc` or unsigned as written in hexadecimal
+    Id(i64),
+    /// A file name, matched by its file ID so case does not matter
+    Name(String),
+}
+
+impl EntryKey {
+    fn id(&self) -> PyResult<i32> {
+        match self {
+            EntryKey::Id(id) if (i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(id) => Ok(*id as u32 as i32),
+            EntryKey::Id(id) => Err(PyValueError::new_err(format!("{} is not a 32-bit file ID", id))),
+            EntryKey::Name(name) => Ok(ra2_crc(name)),
+        }
+    }
+}
+
+/// An entry of an archive
+#[pyclass(module = "ra2_mix", frozen, get_all)]
+#[derive(Debug)]
+struct Entry {
+    /// The file ID
+    id: i32,
+    /// The file name, or `None` if unknown
+    name: Option<String>,
+    /// The size of the data in bytes
+    size: u64,
+}
+
+#[pymethods]
+impl Entry {
+    fn __repr__(&self) -> String {
+        match &self.name {
+            Some(name) => format!("Entry(id=0x{:08X}, name={:?}, size={})", self.id, name, self.size),
+            None => format!("Entry(id=0x{:08X}, name=None, size={})", self.id, self.size),
+        }
+    }
+}
+
+/// A MIX archive held in memory
+///
+/// Entries can be read, written and removed by file name or file ID, through methods or as a mapping.
+#[pyclass(module = "ra2_mix", name = "MixPackage")]
+#[derive(Debug)]
+struct MixPackage {
+    inner: ra2_mix::MixPackage,
+}
+
+#[pymethods]
+impl MixPackage {
+    /// Creates an empty archive for a game, numbered as in the XCC utilities, 5 for Red Alert 2 and 6 for Yuri's Revenge
+    #[new]
+    #[pyo3(signature = (game = 5))]
+    fn new(game: u32) -> Self {
+        Self { inner: ra2_mix::MixPackage { game: XccGame::from(game), ..Default::default() } }
+    }
+    /// Reads an archive from disk
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        Ok(Self { inner: ra2_mix::MixPackage::load(&path).map_err(to_py_err)? })
+    }
+    /// Decodes an archive from bytes
+    #[staticmethod]
+    fn decode(data: &[u8]) -> PyResult<Self> {
+        Ok(Self { inner: ra2_mix::MixPackage::decode(data).map_err(to_py_err)? })
+    }
+    /// The game of the archive, numbered as in the XCC utilities
+    #[getter]
+    fn game(&self) -> u32 {
+        self.inner.game as u32
+    }
+    /// Lists the entries sorted by file ID
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> =
+            self.inner.list().into_iter().map(|entry| Entry { id: entry.id, name: entry.name, size: entry.size }).collect();
+        entries.sort_by_key(|entry| entry.id);
+        entries
+    }
+    /// Reads the data of an entry, raising `KeyError` if it is missing
+    fn read<'py>(&self, py: Python<'py>, entry: EntryKey) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.inner.get_by_id(entry.id()?).ok_or_else(|| missing(&entry))?;
+        Ok(PyBytes::new(py, data))
+    }
+    /// Adds an entry, replacing any entry with the same file ID
+    fn write(&mut self, name: &str, data: &[u8]) {
+        self.inner.insert(name.to_string(), data.to_vec());
+    }
+    /// Removes an entry, raising `KeyError` if it is missing
+    fn remove(&mut self, entry: EntryKey) -> PyResult<()> {
+        match self.inner.remove_by_id(entry.id()?) {
+            Some(_) => Ok(()),
+            None => Err(missing(&entry)),
+        }
+    }
+    /// Encodes the archive into bytes
+    fn encode<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.inner.clone().encode().map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &data))
+    }
+    /// Encodes the archive and writes it to disk, returning the number of bytes written
+    fn save(&self, path: PathBuf) -> PyResult<usize> {
+        self.inner.clone().save(&path).map_err(to_py_err)
+    }
+    fn __len__(&self) -> usize {
+        self.inner.files.len() + self.inner.unnamed.len()
+    }
+    fn __contains__(&self, entry: EntryKey) -> PyResult<bool> {
+        Ok(self.inner.get_by_id(entry.id()?).is_some())
+    }
+    fn __getitem__<'py>(&self, py: Python<'py>, entry: EntryKey) -> PyResult<Bound<'py, PyBytes>> {
+        self.read(py, entry)
+    }
+    fn __setitem__(&mut self, name: &str, data: &[u8]) {
+        self.write(name, data)
+    }
+    fn __delitem__(&mut self, entry: EntryKey) -> PyResult<()> {
+        self.remove(entry)
+    }
+    fn __repr__(&self) -> String {
+        format!("MixPackage(game={:?}, entries={})", self.inner.game, self.__len__())
+    }
+}
+
+fn missing(entry: &EntryKey) -> PyErr {
+    match entry {
+        EntryKey::Id(id) => PyKeyError::new_err(format!("0x{:08X}", *id as u32)),
+        EntryKey::Name(name) => PyKeyError::new_err(name.clone()),
+    }
+}
+
+/// The file ID of a name, as used by the index of an archive
+#[pyfunction(name = "ra2_crc")]
+fn crc(name: &str) -> i32 {
+    ra2_crc(name)
+}
+
+/// Read and write Red Alert 2 MIX archives
+#[pymodule(name = "ra2_mix")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<MixPackage>()?;
+    m.add_class::<Entry>()?;
+    m.add_function(wrap_pyfunction!(crc, m)?)?;
+    m.add("MixError", py.get_type::<MixError>())?;
+    m.add("TruncatedError", py.get_type::<TruncatedError>())?;
+    m.add("InvalidIndexError", py.get_type::<InvalidIndexError>())?;
+    m.add("CryptoError", py.get_type::<CryptoError>())?;
+    m.add("InvalidFormatError", py.get_type::<InvalidFormatError>())?;
+    m.add("NameCollisionError", py.get_type::<NameCollisionError>())?;
+    m.add("EntryConflictError", py.get_type::<EntryConflictError>())?;
+    m.add("TooManyFilesError", py.get_type::<TooManyFilesError>())?;
+    m.add("BodyTooLargeError", py.get_type::<BodyTooLargeError>())?;
+    m.add("UnsupportedError", py.get_type::<UnsupportedError>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+import pytest
+
+import ra2_mix
+
+RULES = b"[General]\r\nName=Test\r\n"
+
+
+@pytest.fixture
+def package():
+    mix = ra2_mix.MixPackage(game=6)
+    mix.write("rulesmd.ini", RULES)
+    mix["artmd.ini"] = b"[Art]"
+    return mix
+
+
+def test_ra2_crc():
+    assert ra2_mix.ra2_crc("rulesmd.ini") == ra2_mix.ra2_crc("RULESMD.INI")
+    assert ra2_mix.ra2_crc("local mix database.dat") & 0xFFFFFFFF == 0x366E051F
+
+
+def test_entries(package):
+    entries = package.entries()
+    assert sorted(entry.name for entry in entries) == ["artmd.ini", "rulesmd.ini"]
+    assert [entry.id for entry in entries] == sorted(entry.id for entry in entries)
+    rules = next(entry for entry in entries if entry.name == "rulesmd.ini")
+    assert rules.id == ra2_mix.ra2_crc("rulesmd.ini")
+    assert rules.size == len(RULES)
+    assert len(package) == 2
+    assert "RulesMD.ini" in package
+    assert "missing.ini" not in package
+
+
+def test_read_by_name_and_id(package):
+    assert package.read("rulesmd.ini") == RULES
+    assert package["RULESMD.INI"] == RULES
+    signed = ra2_mix.ra2_crc("rulesmd.ini")
+    assert package[signed] == RULES
+    assert package[signed & 0xFFFFFFFF] == RULES
+    with pytest.raises(KeyError):
+        package["missing.ini"]
+    with pytest.raises(ValueError):
+        package[1 << 40]
+
+
+def test_write_replaces_and_remove(package):
+    package["RULESMD.INI"] = b"[General]"
+    assert len(package) == 2
+    assert package["rulesmd.ini"] == b"[General]"
+    del package["artmd.ini"]
+    assert len(package) == 1
+    with pytest.raises(KeyError):
+        package.remove("artmd.ini")
+
+
+def test_round_trip(package, tmp_path):
+    decoded = ra2_mix.MixPackage.decode(package.encode())
+    assert decoded["rulesmd.ini"] == RULES
+    assert decoded.game == 6
+
+    path = tmp_path / "expandmd01.mix"
+    assert package.save(path) == path.stat().st_size
+    loaded = ra2_mix.MixPackage.load(str(path))
+    assert {entry.name for entry in loaded.entries()} == {"rulesmd.ini", "artmd.ini"}
+
+
+def test_errors(tmp_path):
+    with pytest.raises(FileNotFoundError):
+        ra2_mix.MixPackage.load(tmp_path / "missing.mix")
+    with pytest.raises(ra2_mix.MixError) as error:
+        ra2_mix.MixPackage.decode(b"\x01\x00")
+    assert isinstance(error.value, (ra2_mix.TruncatedError, ra2_mix.InvalidFormatError))
+    assert issubclass(ra2_mix.InvalidIndexError, ra2_mix.MixError)
@@ -0,0 +1,96 @@
+#![deny(missing_debug_implementations, missing_copy_implementations)]
+#![warn(missing_docs)]
+#![doc = include_str!("../readme.md")]
+
+use ra2_mix::{ArchiveSource, MixPackage, XccGame, ra2_crc};
+use wasm_bindgen::prelude::*;
+
+/// The file ID of a name, as used by the index of an archive
+#[wasm_bindgen(js_name = ra2Crc)]
+pub fn crc(name: &str) -> i32 {
+    ra2_crc(name)
+}
+
+/// An entry of an archive
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The file ID, use `id >>> 0` for the unsigned value
+    pub id: i32,
+    /// The file name, or `undefined` if unknown
+    pub name: Option<String>,
+    /// The size of the data in bytes, a JavaScript number is exact for any size
+    pub size: f64,
+}
+
+/// A MIX archive held in memory
+#[wasm_bindgen]
+#[derive(Debug, Default)]
+pub struct MixArchive {
+    package: MixPackage,
+}
+
+#[wasm_bindgen]
+impl MixArchive {
+    /// Creates an empty archive for a game, numbered as in the XCC utilities, 5 for Red Alert 2 and 6 for Yuri's Revenge
+    #[wasm_bindgen(constructor)]
+    pub fn new(game: Option<u32>) -> Self {
+        Self { package: MixPackage { game: game.map_or_else(XccGame::default, XccGame::from), ..Default::default() } }
+    }
+    /// Decodes an archive, throwing an `Error` if it is invalid
+    pub fn decode(data: &[u8]) -> Result<MixArchive, JsError> {
+        Ok(Self { package: MixPackage::decode(data)? })
+    }
+    /// The game of the archive, numbered as in the XCC utilities
+    #[wasm_bindgen(getter)]
+    pub fn game(&self) -> u32 {
+        self.package.game as u32
+    }
+    /// Lists the entries sorted by file ID
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .package
+            .list()
+            .into_iter()
+            .map(|entry| Entry { id: entry.id, name: entry.name, size: entry.size as f64 })
+            .collect();
+        entries.sort_by_key(|entry| entry.id);
+        entries
+    }
+    /// Reads an entry by file name, `undefined` if it is missing
+    pub fn get(&self, name: &str) -> Option<Vec<u8>> {
+        self.get_by_id(ra2_crc(name))
+    }
+    /// Reads an entry by file ID, `undefined` if it is missing
+    #[wasm_bindgen(js_name = getById)]
+    pub fn get_by_id(&self, id: i32) -> Option<Vec<u8>> {
+        self.package.get_by_id(id).map(<[u8]>::to_vec)
+    }
+    /// Adds an entry, replacing any entry with the same file ID
+    pub fn set(&mut self, name: &str, data: &[u8]) {
+        self.package.insert(name.to_string(), data.to_vec());
+    }
+    /// Removes an entry by file name, returning whether it existed
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.remove_by_id(ra2_crc(name))
+    }
+    /// Removes an entry by file ID, returning whether it existed
+    #[wasm_bindgen(js_name = removeById)]
+    pub fn remove_by_id(&mut self, id: i32) -> bool {
+        self.package.remove_by_id(id).is_some()
+    }
+    /// The number of entries
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.package.files.len() + self.package.unnamed.len()
+    }
+    /// Checks if the archive has no entries
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Encodes the archive, throwing an `Error` if it exceeds the limits of the format
+    pub fn encode(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.package.clone().encode()?)
+    }
+}
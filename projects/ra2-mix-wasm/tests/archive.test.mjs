@@ -0,0 +1,41 @@
+// Runs under Node after `wasm-pack build --target nodejs --out-dir pkg`, see `npm test`
+import assert from "node:assert/strict";
+import { createRequire } from "node:module";
+import test from "node:test";
+
+const { MixArchive, ra2Crc } = createRequire(import.meta.url)("../pkg/ra2_mix_wasm.js");
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+test("encodes and decodes an archive", () => {
+    const archive = new MixArchive(6);
+    archive.set("rulesmd.ini", encoder.encode("[General]\r\nName=Test"));
+    archive.set("artmd.ini", encoder.encode("[Art]"));
+    assert.equal(archive.len, 2);
+
+    const bytes = archive.encode();
+    assert.ok(bytes instanceof Uint8Array);
+    const decoded = MixArchive.decode(bytes);
+    assert.equal(decoded.game, 6);
+
+    const names = decoded.entries().map((entry) => entry.name).sort();
+    assert.deepEqual(names, ["artmd.ini", "rulesmd.ini"]);
+    const rules = decoded.entries().find((entry) => entry.id === ra2Crc("RULESMD.INI"));
+    assert.equal(rules.size, 20);
+    assert.equal(decoder.decode(decoded.get("rulesmd.ini")), "[General]\r\nName=Test");
+    assert.equal(decoder.decode(decoded.getById(ra2Crc("artmd.ini"))), "[Art]");
+    assert.equal(decoded.get("missing.ini"), undefined);
+});
+
+test("removes entries", () => {
+    const archive = new MixArchive();
+    archive.set("rulesmd.ini", new Uint8Array([1, 2, 3]));
+    assert.equal(archive.remove("RULESMD.INI"), true);
+    assert.equal(archive.remove("rulesmd.ini"), false);
+    assert.equal(archive.isEmpty(), true);
+});
+
+test("throws on invalid archives", () => {
+    assert.throws(() => MixArchive.decode(new Uint8Array([1, 0])), Error);
+});
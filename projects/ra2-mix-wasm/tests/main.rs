@@ -0,0 +1,25 @@
+use ra2_mix_wasm::{MixArchive, crc};
+
+#[test]
+fn round_trip() {
+    let mut archive = MixArchive::new(Some(6));
+    archive.set("rulesmd.ini", b"[General]");
+    archive.set("artmd.ini", b"[Art]");
+    archive.set("RULESMD.INI", b"[General]\r\nName=Test");
+    assert_eq!(archive.len(), 2);
+
+    let decoded = MixArchive::decode(&archive.encode().unwrap()).unwrap();
+    assert_eq!(decoded.game(), 6);
+    let entries = decoded.entries();
+    assert!(entries.windows(2).all(|pair| pair[0].id < pair[1].id));
+    let rules = entries.iter().find(|entry| entry.id == crc("rulesmd.ini")).unwrap();
+    assert_eq!(rules.size, 20.0);
+    assert_eq!(decoded.get("RulesMD.ini").as_deref(), Some(&b"[General]\r\nName=Test"[..]));
+    assert_eq!(decoded.get_by_id(crc("artmd.ini")).as_deref(), Some(&b"[Art]"[..]));
+    assert_eq!(decoded.get("missing.ini"), None);
+
+    let mut decoded = decoded;
+    assert!(decoded.remove("artmd.ini"));
+    assert!(!decoded.remove_by_id(crc("artmd.ini")));
+    assert_eq!(decoded.len(), 1);
+}
@@ -0,0 +1,416 @@
+//! Interactive terminal browser for archives and game folders
+//!
+//! Keys: arrows or `j`/`k` move, `Enter` opens a nested archive, `Esc` goes back, `Tab` switches between text and
+//! hex preview, `x` extracts the selected entry, `r` replaces it with a file, `s` saves changes and `q` quits.
+
+use ra2_mix::{ArchiveSource, DirectorySource, EntryKind, MixError, MixPackage};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, TableState},
+};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// The number of bytes shown in the preview
+const PREVIEW_SIZE: u64 = 64 * 1024;
+
+/// Opens the browser on a MIX file or a folder until the user quits
+pub fn run(path: &Path, output: &Path) -> Result<(), MixError> {
+    let mut app = App::open(path, output)?;
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// What a level shows
+enum Container {
+    /// The files of a folder
+    Directory(DirectorySource),
+    /// The entries of an archive
+    Mix(Box<MixPackage>),
+}
+
+/// Where the changes of a level are written to
+enum Origin {
+    /// The level is a file on disk
+    File(PathBuf),
+    /// The level is an entry of the parent level
+    Entry(Entry),
+}
+
+/// An entry of a level
+#[derive(Clone)]
+struct Entry {
+    id: i32,
+    name: Option<String>,
+    size: u64,
+    kind: EntryKind,
+}
+
+impl Entry {
+    /// The name of the entry, or its ID in hexadecimal
+    fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{:08X}", self.id))
+    }
+}
+
+/// An open folder or archive
+struct Level {
+    title: String,
+    container: Container,
+    origin: Origin,
+    entries: Vec<Entry>,
+    state: TableState,
+    dirty: bool,
+}
+
+impl Level {
+    fn new(title: String, container: Container, origin: Origin) -> Self {
+        let mut level = Self { title, container, origin, entries: Vec::new(), state: TableState::default(), dirty: false };
+        level.refresh();
+        level
+    }
+    /// Opens a folder or a MIX file
+    fn open(path: &Path) -> Result<Self, MixError> {
+        let title = path.display().to_string();
+        let container = if path.is_dir() {
+            Container::Directory(DirectorySource::new(path)?)
+        }
+        else {
+            Container::Mix(Box::new(MixPackage::load(path)?))
+        };
+        Ok(Level::new(title, container, Origin::File(path.to_path_buf())))
+    }
+    /// Lists the entries again, keeping the selection in range
+    fn refresh(&mut self) {
+        let source: &dyn ArchiveSource = match &self.container {
+            Container::Directory(directory) => directory,
+            Container::Mix(package) => package.as_ref(),
+        };
+        let mut entries: Vec<Entry> = source
+            .list()
+            .into_iter()
+            .map(|entry| Entry { id: entry.id, name: entry.name, size: entry.size, kind: EntryKind::Unknown })
+            .collect();
+        entries.sort_by(|a, b| (a.name.is_none(), &a.name, a.id).cmp(&(b.name.is_none(), &b.name, b.id)));
+        for entry in &mut entries {
+            entry.kind = match &self.container {
+                // Nested archives are only recognized from their whole data, which is already in memory
+                Container::Mix(package) => EntryKind::detect(entry.name.as_deref(), entry_data(package, entry)),
+                // Files always have a name, the start of the file only matters for unknown extensions
+                Container::Directory(_) => {
+                    EntryKind::detect(entry.name.as_deref(), &self.read(entry, 4096).unwrap_or_default())
+                }
+            };
+        }
+        self.entries = entries;
+        let selected = self.state.selected().unwrap_or(0).min(self.entries.len().saturating_sub(1));
+        self.state.select(if self.entries.is_empty() { None } else { Some(selected) });
+    }
+    fn selected(&self) -> Option<&Entry> {
+        self.state.selected().and_then(|index| self.entries.get(index))
+    }
+    /// Reads up to `limit` bytes of an entry
+    fn read(&self, entry: &Entry, limit: u64) -> Result<Vec<u8>, MixError> {
+        let mut data = Vec::new();
+        match &self.container {
+            Container::Directory(directory) => {
+                let name = entry.name.as_deref().unwrap_or_default();
+                std::fs::File::open(directory.root().join(name))?.take(limit).read_to_end(&mut data)?;
+            }
+            Container::Mix(package) => {
+                data.extend(entry_data(package, entry).iter().take(limit as usize));
+            }
+        }
+        Ok(data)
+    }
+    /// Replaces the data of an entry
+    fn write(&mut self, entry: &Entry, data: Vec<u8>) -> Result<(), MixError> {
+        match &mut self.container {
+            Container::Directory(directory) => {
+                std::fs::write(directory.root().join(entry.label()), data)?;
+                *directory = DirectorySource::new(directory.root())?;
+            }
+            Container::Mix(package) => {
+                match &entry.name {
+                    Some(name) => package.files.insert(name.clone(), data),
+                    None => package.unnamed.insert(entry.id, data),
+                };
+                self.dirty = true;
+            }
+        }
+        self.refresh();
+        Ok(())
+    }
+}
+
+/// The data of an entry of an archive, empty if it is gone
+fn entry_data<'a>(package: &'a MixPackage, entry: &Entry) -> &'a [u8] {
+    let data = match &entry.name {
+        Some(name) => package.files.get(name),
+        None => package.unnamed.get(&entry.id),
+    };
+    data.map_or(&[], |data| data.as_slice())
+}
+
+/// What the text input is for
+#[derive(Clone, Copy)]
+enum Prompt {
+    /// The path of the file that replaces the selected entry
+    Replace,
+}
+
+struct App {
+    levels: Vec<Level>,
+    hex: bool,
+    input: Option<(Prompt, String)>,
+    status: String,
+    output: PathBuf,
+    quit_requested: bool,
+    quit: bool,
+}
+
+impl App {
+    fn open(path: &Path, output: &Path) -> Result<Self, MixError> {
+        Ok(Self {
+            levels: vec![Level::open(path)?],
+            hex: false,
+            input: None,
+            status: "Enter: open  Esc: back  Tab: hex/text  x: extract  r: replace  s: save  q: quit".to_string(),
+            output: output.to_path_buf(),
+            quit_requested: false,
+            quit: false,
+        })
+    }
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), MixError> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    if let Err(e) = self.handle(key.code) {
+                        self.status = format!("Error: {}", e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn level(&self) -> &Level {
+        self.levels.last().expect("the root level is never closed")
+    }
+    fn level_mut(&mut self) -> &mut Level {
+        self.levels.last_mut().expect("the root level is never closed")
+    }
+    fn handle(&mut self, key: KeyCode) -> Result<(), MixError> {
+        if let Some((prompt, mut buffer)) = self.input.take() {
+            match key {
+                KeyCode::Enter => return self.submit(prompt, &buffer),
+                KeyCode::Esc => self.status = "Cancelled".to_string(),
+                KeyCode::Backspace => {
+                    buffer.pop();
+                    self.input = Some((prompt, buffer));
+                }
+                KeyCode::Char(c) => {
+                    buffer.push(c);
+                    self.input = Some((prompt, buffer));
+                }
+                _ => self.input = Some((prompt, buffer)),
+            }
+            return Ok(());
+        }
+        if key != KeyCode::Char('q') {
+            self.quit_requested = false;
+        }
+        let level = self.level_mut();
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => level.state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => level.state.select_next(),
+            KeyCode::PageUp => level.state.scroll_up_by(20),
+            KeyCode::PageDown => level.state.scroll_down_by(20),
+            KeyCode::Home => level.state.select_first(),
+            KeyCode::End => level.state.select_last(),
+            KeyCode::Enter | KeyCode::Right => self.enter()?,
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Left => self.leave()?,
+            KeyCode::Tab => self.hex = !self.hex,
+            KeyCode::Char('x') => self.extract()?,
+            KeyCode::Char('r') if self.level().selected().is_some() => {
+                self.input = Some((Prompt::Replace, String::new()));
+            }
+            KeyCode::Char('s') => self.save()?,
+            KeyCode::Char('q') => {
+                if self.levels.iter().any(|level| level.dirty) && !self.quit_requested {
+                    self.quit_requested = true;
+                    self.status = "Unsaved changes, press q again to quit without saving or s to save".to_string();
+                }
+                else {
+                    self.quit = true;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    fn submit(&mut self, prompt: Prompt, buffer: &str) -> Result<(), MixError> {
+        match prompt {
+            Prompt::Replace => {
+                let entry = match self.level().selected() {
+                    Some(entry) => entry.clone(),
+                    None => return Ok(()),
+                };
+                let context = |e: std::io::Error| MixError::from(e).context(format!("Failed to read `{}`", buffer));
+                let data = std::fs::read(buffer).map_err(context)?;
+                self.level_mut().write(&entry, data)?;
+                self.status = format!("Replaced {} with {}", entry.label(), buffer);
+            }
+        }
+        Ok(())
+    }
+    /// Opens the selected entry if it is an archive
+    fn enter(&mut self) -> Result<(), MixError> {
+        let level = self.level();
+        let entry = match level.selected() {
+            Some(entry) if entry.kind == EntryKind::Mix => entry.clone(),
+            _ => return Ok(()),
+        };
+        let title = entry.label();
+        let next = match &level.container {
+            Container::Directory(directory) => Level::open(&directory.root().join(&title))?,
+            Container::Mix(_) => {
+                let package = MixPackage::decode(&level.read(&entry, u64::MAX)?)?;
+                Level::new(title, Container::Mix(Box::new(package)), Origin::Entry(entry))
+            }
+        };
+        self.levels.push(next);
+        Ok(())
+    }
+    /// Goes back to the parent level, handing it the changes of the nested archive
+    fn leave(&mut self) -> Result<(), MixError> {
+        if self.levels.len() == 1 {
+            return Ok(());
+        }
+        if self.level().dirty {
+            if let Origin::Entry(_) = self.level().origin {
+                self.commit(self.levels.len() - 1)?;
+            }
+        }
+        if !self.level().dirty {
+            self.levels.pop();
+        }
+        else {
+            self.status = "Unsaved changes, press s to save before going back".to_string();
+        }
+        Ok(())
+    }
+    /// Encodes a changed level into its parent entry or its file
+    fn commit(&mut self, index: usize) -> Result<(), MixError> {
+        let level = &self.levels[index];
+        let package = match &level.container {
+            Container::Mix(package) => package.as_ref().clone(),
+            Container::Directory(_) => return Ok(()),
+        };
+        let data = package.encode()?;
+        match &level.origin {
+            Origin::File(path) => replace_file(path, &data)?,
+            Origin::Entry(entry) => {
+                let entry = entry.clone();
+                self.levels[index - 1].write(&entry, data)?;
+            }
+        }
+        self.levels[index].dirty = false;
+        Ok(())
+    }
+    /// Writes every change down to the files on disk
+    fn save(&mut self) -> Result<(), MixError> {
+        for index in (0..self.levels.len()).rev() {
+            if self.levels[index].dirty {
+                self.commit(index)?;
+            }
+        }
+        self.status = "Saved".to_string();
+        Ok(())
+    }
+    /// Writes the selected entry to the output folder
+    fn extract(&mut self) -> Result<(), MixError> {
+        let level = self.level();
+        let entry = match level.selected() {
+            Some(entry) => entry.clone(),
+            None => return Ok(()),
+        };
+        let data = level.read(&entry, u64::MAX)?;
+        std::fs::create_dir_all(&self.output)?;
+        let path = ra2_mix::extract_path(&self.output, &entry.label(), entry.id);
+        std::fs::write(&path, data)?;
+        self.status = format!("Extracted {}", path.display());
+        Ok(())
+    }
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list, preview] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(main);
+
+        let title = self.levels.iter().map(|level| level.title.as_str()).collect::<Vec<_>>().join(" > ");
+        let dirty = if self.levels.iter().any(|level| level.dirty) { " [modified]" } else { "" };
+        let level = self.levels.last_mut().expect("the root level is never closed");
+        let rows = level.entries.iter().map(|entry| {
+            Row::new([format!("{:08X}", entry.id), entry.label(), entry.size.to_string(), entry.kind.to_string()])
+        });
+        let widths = [Constraint::Length(8), Constraint::Fill(1), Constraint::Length(10), Constraint::Length(4)];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["ID", "Name", "Size", "Type"]).bold())
+            .block(Block::bordered().title(format!("{}{}", title, dirty)))
+            .row_highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(table, list, &mut level.state);
+
+        let level = self.level();
+        let (preview_title, lines) = match level.selected() {
+            Some(entry) => {
+                let data = level.read(entry, PREVIEW_SIZE).unwrap_or_default();
+                if self.hex || !(entry.kind.is_text() || entry.kind == EntryKind::Unknown && !data.contains(&0)) {
+                    (format!("{} (hex)", entry.label()), hex_lines(&data))
+                }
+                else {
+                    let text = String::from_utf8_lossy(&data).replace('\r', "");
+                    (format!("{} (text)", entry.label()), text.lines().map(|line| Line::from(line.to_string())).collect())
+                }
+            }
+            None => (String::new(), Vec::new()),
+        };
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(preview_title)), preview);
+
+        let status_line = match &self.input {
+            Some((Prompt::Replace, buffer)) => format!("Replace with file: {}_", buffer),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// Writes a file through a hidden file next to it renamed over it, so a failed write keeps the previous file
+fn replace_file(path: &Path, data: &[u8]) -> Result<(), MixError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.tmp", name));
+    let result = std::fs::write(&temporary, data).and_then(|()| std::fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    Ok(result?)
+}
+
+/// Formats data as 16 bytes per line with offsets and printable characters
+fn hex_lines(data: &[u8]) -> Vec<Line<'static>> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let printable = |&byte: &u8| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+            let ascii: String = chunk.iter().map(printable).collect();
+            Line::from(format!("{:08X}  {:<47}  {}", row * 16, hex.join(" "), ascii))
+        })
+        .collect()
+}
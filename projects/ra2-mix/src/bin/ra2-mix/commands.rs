@@ -0,0 +1,366 @@
+//! Implementation of the subcommands
+
+use crate::{EntryArg, Format, Outcome, WriteOptions};
+use ra2_mix::{
+    ArchiveSource, CancellationToken, Catalog, MixError, MixIndex, MixListing, MixPackage, MixReader, MixTask, MixWarning,
+    MixWatcher, SearchQuery, ShadowCopy, ShadowReport, ra2_crc,
+};
+use serde_json::{Value, json};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Prints results as text or JSON
+pub struct Output {
+    /// Whether to print JSON
+    pub json: bool,
+}
+
+impl Output {
+    /// Prints the JSON value, or the text in text mode
+    fn emit(&self, value: Value, text: impl FnOnce() -> String) {
+        if self.json {
+            println!("{}", value);
+        }
+        else {
+            print!("{}", text());
+        }
+    }
+    /// Reports a failed command, as JSON on stdout in JSON mode and as text on stderr otherwise
+    pub fn error(&self, error: &MixError) {
+        if self.json {
+            println!("{}", json!({ "error": error.to_string() }));
+        }
+        else {
+            eprintln!("error: {}", error);
+        }
+    }
+}
+
+fn warnings_json(warnings: &[MixWarning]) -> Value {
+    warnings.iter().map(|warning| json!({ "code": warning.code(), "message": warning.to_string() })).collect()
+}
+
+fn warnings_text(warnings: &[MixWarning]) -> String {
+    warnings.iter().map(|warning| format!("warning: {}\n", warning)).collect()
+}
+
+/// Lists the files of the inputs, folders giving the files directly inside them
+fn collect_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, MixError> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut entries = Vec::new();
+            for entry in std::fs::read_dir(input)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    entries.push(path);
+                }
+            }
+            entries.sort();
+            files.extend(entries);
+        }
+        else {
+            files.push(input.clone());
+        }
+    }
+    Ok(files)
+}
+
+pub fn list(mix: &Path, output: &Output) -> Result<Outcome, MixError> {
+    let reader = MixReader::open(mix)?;
+    let entries = reader.index().entries.iter().map(|entry| {
+        json!({ "id": format!("{:08X}", entry.id), "name": reader.name(entry.id), "offset": entry.offset, "size": entry.size })
+    });
+    let value = json!({ "entries": entries.collect::<Vec<_>>(), "warnings": warnings_json(reader.warnings()) });
+    output.emit(value, || {
+        let mut text = warnings_text(reader.warnings());
+        for entry in &reader.index().entries {
+            let name = reader.name(entry.id).unwrap_or("");
+            text.push_str(&format!("{:08X} {:>10} {:>10} {}\n", entry.id, entry.offset, entry.size, name));
+        }
+        text
+    });
+    Ok(Outcome::Done)
+}
+
+pub fn info(mix: &Path, output: &Output) -> Result<Outcome, MixError> {
+    let reader = MixReader::open(mix)?;
+    let index = reader.index();
+    let header = index.header;
+    let names = reader.database().map(|database| database.names.len());
+    let value = json!({
+        "format": if header.flags.is_some() { "new" } else { "old" },
+        "flags": header.flags,
+        "encrypted": header.is_encrypted(),
+        "checksum": header.has_checksum(),
+        "entries": header.file_count,
+        "body_offset": index.body_offset,
+        "body_size": header.data_size,
+        "game": format!("{:?}", reader.game()),
+        "database_names": names,
+        "warnings": warnings_json(reader.warnings()),
+    });
+    output.emit(value, || {
+        let mut text = warnings_text(reader.warnings());
+        text.push_str(&format!("Format:      {}\n", if header.flags.is_some() { "new" } else { "old" }));
+        text.push_str(&format!("Encrypted:   {}\n", header.is_encrypted()));
+        text.push_str(&format!("Checksum:    {}\n", header.has_checksum()));
+        text.push_str(&format!("Entries:     {}\n", header.file_count));
+        text.push_str(&format!("Body:        {} bytes at {}\n", header.data_size, index.body_offset));
+        text.push_str(&format!("Game:        {:?}\n", reader.game()));
+        match names {
+            Some(names) => text.push_str(&format!("Database:    {} names\n", names)),
+            None => text.push_str("Database:    none\n"),
+        }
+        text
+    });
+    Ok(Outcome::Done)
+}
+
+pub fn extract(mix: &Path, folder: &Path, entries: &[EntryArg], output: &Output) -> Result<Outcome, MixError> {
+    let mut written = Vec::new();
+    if entries.is_empty() {
+        ra2_mix::extract_with(mix, folder, &mut MixTask::new())?;
+        // The database itself is never extracted
+        let reader = MixReader::open(mix)?;
+        let database_id = ra2_crc("local mix database.dat");
+        let entries = reader.index().entries.iter().filter(|entry| entry.id != database_id);
+        let mut names: Vec<(&str, i32)> = entries.filter_map(|entry| Some((reader.name(entry.id)?, entry.id))).collect();
+        names.sort();
+        written.extend(names.into_iter().map(|(name, id)| ra2_mix::extract_path(folder, name, id)));
+    }
+    else {
+        let mut reader = MixReader::open(mix)?;
+        std::fs::create_dir_all(folder)?;
+        for entry in entries {
+            let id = entry.id;
+            let name = match reader.name(id) {
+                Some(name) => name.to_string(),
+                None if entry.is_id() => format!("{:08X}", id),
+                None => entry.text.clone(),
+            };
+            let data = match reader.read_entry(id) {
+                Err(MixError::EntryNotFound(_)) => return Err(MixError::FileNotFound(PathBuf::from(&entry.text))),
+                result => result?,
+            };
+            let path = ra2_mix::extract_path(folder, &name, id);
+            std::fs::write(&path, data)?;
+            written.push(path);
+        }
+    }
+    let value = json!({ "extracted": written.iter().map(|path| path.display().to_string()).collect::<Vec<_>>() });
+    output.emit(value, || written.iter().map(|path| format!("{}\n", path.display())).collect());
+    Ok(Outcome::Done)
+}
+
+/// Saves the package and reports what was written
+fn save(package: MixPackage, mix: &Path, output: &Output) -> Result<Outcome, MixError> {
+    let entries = package.files.len() + package.unnamed.len();
+    let size = package.save(mix)?;
+    let value = json!({ "archive": mix.display().to_string(), "entries": entries, "size": size });
+    output.emit(value, || format!("Wrote {} entries, {} bytes to {}\n", entries, size, mix.display()));
+    Ok(Outcome::Done)
+}
+
+pub fn create(mix: &Path, inputs: &[PathBuf], options: &WriteOptions, output: &Output) -> Result<Outcome, MixError> {
+    let mut package = MixPackage {
+        game: options.game.into(),
+        database_policy: options.database.into(),
+        checksum: options.checksum,
+        ..Default::default()
+    };
+    for file in collect_inputs(inputs)? {
+        package.add_file(&file)?;
+    }
+    save(package, mix, output)
+}
+
+pub fn add(mix: &Path, inputs: &[PathBuf], output: &Output) -> Result<Outcome, MixError> {
+    let mut package = MixPackage::load(mix)?;
+    for file in collect_inputs(inputs)? {
+        let name = match file.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => return Err(MixError::FileNotFound(file)),
+        };
+        // Replaces entries with the same file ID, named in another case or unnamed
+        package.insert(name, std::fs::read(&file)?);
+    }
+    save(package, mix, output)
+}
+
+pub fn remove(mix: &Path, entries: &[EntryArg], output: &Output) -> Result<Outcome, MixError> {
+    let mut package = MixPackage::load(mix)?;
+    for entry in entries {
+        let id = entry.id;
+        let count = package.files.len() + package.unnamed.len();
+        package.files.retain(|name, _| ra2_crc(name) != id);
+        package.unnamed.remove(&id);
+        if package.files.len() + package.unnamed.len() == count {
+            return Err(MixError::FileNotFound(PathBuf::from(&entry.text)));
+        }
+    }
+    save(package, mix, output)
+}
+
+pub fn patch(input: &Path, mix: &Path, output: &Output) -> Result<Outcome, MixError> {
+    ra2_mix::patch_with(input, mix, &mut MixTask::new())?;
+    let reader = MixReader::open(mix)?;
+    let entries = reader.list().len();
+    let value = json!({ "archive": mix.display().to_string(), "entries": entries });
+    output.emit(value, || format!("Patched {} into {}, now {} entries\n", input.display(), mix.display(), entries));
+    Ok(Outcome::Done)
+}
+
+pub fn export(mix: &Path, format: Format) -> Result<Outcome, MixError> {
+    let listing = MixListing::open(mix)?;
+    match format {
+        Format::Json => println!("{}", listing.to_json()?),
+        Format::Csv => print!("{}", listing.to_csv()),
+    }
+    Ok(Outcome::Done)
+}
+
+pub fn verify(mix: &Path, output: &Output) -> Result<Outcome, MixError> {
+    let data = std::fs::read(mix)?;
+    let index = MixIndex::parse(&data)?;
+    let body_size = (data.len() as u64).saturating_sub(index.body_offset);
+    let mut problems: Vec<(&str, String)> = Vec::new();
+
+    if u64::from(index.header.data_size) > body_size {
+        let message = format!("The header promises {} body bytes, {} found", index.header.data_size, body_size);
+        problems.push(("truncated-body", message));
+    }
+    let mut ids = HashSet::new();
+    for entry in &index.entries {
+        if !ids.insert(entry.id) {
+            problems.push(("duplicate-id", format!("Entry {:08X} appears more than once", entry.id)));
+        }
+        if let Err(e) = entry.range(body_size) {
+            problems.push(("entry-out-of-bounds", e.to_string()));
+        }
+    }
+    if !index.entries.windows(2).all(|pair| pair[0].id <= pair[1].id) {
+        problems.push(("unsorted-index", "The index is not sorted by ID, the game will miss entries".to_string()));
+    }
+    let mut by_offset: Vec<_> = index.entries.iter().filter(|entry| entry.size > 0).collect();
+    by_offset.sort_by_key(|entry| (entry.offset, entry.size));
+    for pair in by_offset.windows(2) {
+        if i64::from(pair[0].offset) + i64::from(pair[0].size) > i64::from(pair[1].offset) {
+            problems.push(("overlapping-entries", format!("Entries {:08X} and {:08X} overlap", pair[0].id, pair[1].id)));
+        }
+    }
+    if index.verify_checksum(&data) == Some(false) {
+        problems.push(("checksum-mismatch", "The SHA-1 digest does not match the body".to_string()));
+    }
+    if let Err(e) = MixPackage::decode_with_warnings(&data) {
+        problems.push(("unreadable", e.to_string()));
+    }
+
+    let value = json!({
+        "ok": problems.is_empty(),
+        "problems": problems.iter().map(|(code, message)| json!({ "code": code, "message": message })).collect::<Vec<_>>(),
+    });
+    output.emit(value, || {
+        if problems.is_empty() {
+            return format!("{}: OK\n", mix.display());
+        }
+        problems.iter().map(|(code, message)| format!("{}: {}: {}\n", mix.display(), code, message)).collect()
+    });
+    Ok(if problems.is_empty() { Outcome::Done } else { Outcome::Problems })
+}
+
+pub fn search(query: &SearchQuery, inputs: &[PathBuf], output: &Output) -> Result<Outcome, MixError> {
+    let hits = ra2_mix::search(inputs, query)?;
+    let matches = hits.iter().map(|hit| {
+        json!({
+            "chain": hit.chain(),
+            "id": format!("{:08X}", hit.entry.id),
+            "name": hit.entry.name,
+            "offset": hit.entry.offset,
+            "size": hit.entry.size,
+            "hash": format!("{:08X}", hit.entry.hash),
+        })
+    });
+    output.emit(json!({ "matches": matches.collect::<Vec<_>>() }), || {
+        let mut text = String::new();
+        for hit in &hits {
+            let name = hit.entry.name.clone().unwrap_or_else(|| format!("{:08X}", hit.entry.id));
+            let location = hit.chain().into_iter().chain([name]).collect::<Vec<_>>().join(" > ");
+            text.push_str(&format!("{:08X} {:08X} {:>10} {}\n", hit.entry.id, hit.entry.hash, hit.entry.size, location));
+        }
+        text
+    });
+    Ok(Outcome::Done)
+}
+
+pub fn duplicates(folder: &Path, output: &Output) -> Result<Outcome, MixError> {
+    let report = ShadowReport::new(&Catalog::build(folder)?);
+    let copy_json = |copy: &ShadowCopy| {
+        json!({
+            "chain": copy.chain,
+            "size": copy.size,
+            "hash": format!("{:08X}", copy.hash),
+            "identical": copy.identical,
+        })
+    };
+    let groups = report.groups.iter().map(|group| {
+        json!({
+            "id": format!("{:08X}", group.id),
+            "name": group.name,
+            "winner": copy_json(&group.winner),
+            "shadowed": group.shadowed.iter().map(copy_json).collect::<Vec<_>>(),
+            "not_loaded": group.not_loaded.iter().map(copy_json).collect::<Vec<_>>(),
+        })
+    });
+    let value = json!({
+        "duplicates": groups.collect::<Vec<_>>(),
+        "identical": report.identical,
+        "different": report.different,
+        "wasted": report.wasted,
+    });
+    output.emit(value, || {
+        let mut text = String::new();
+        for group in &report.groups {
+            let name = group.name.clone().unwrap_or_else(|| format!("{:08X}", group.id));
+            text.push_str(&format!("{:08X} {}\n", group.id, name));
+            text.push_str(&format!("  loads     {:>10} {}\n", group.winner.size, group.winner.chain.join(" > ")));
+            for copy in &group.shadowed {
+                let state = if copy.identical { "identical" } else { "different" };
+                text.push_str(&format!("  {} {:>10} {}\n", state, copy.size, copy.chain.join(" > ")));
+            }
+            for copy in &group.not_loaded {
+                text.push_str(&format!("  not loaded {:>9} {}\n", copy.size, copy.chain.join(" > ")));
+            }
+        }
+        text.push_str(&format!(
+            "{} duplicated files, {} identical and {} different shadowed copies, {} bytes wasted\n",
+            report.groups.len(),
+            report.identical,
+            report.different,
+            report.wasted
+        ));
+        text
+    });
+    Ok(Outcome::Done)
+}
+
+pub fn watch(input: &Path, mix: &Path, debounce: u64, output: &Output) -> Result<Outcome, MixError> {
+    let mut watcher = MixWatcher::new(input, mix)?.with_debounce(Duration::from_millis(debounce));
+    watcher.watch(&CancellationToken::new(), |rebuild| match rebuild {
+        Ok(summary) => {
+            let value = json!({
+                "added": summary.added,
+                "updated": summary.updated,
+                "removed": summary.removed,
+                "written": summary.written,
+            });
+            output.emit(value, || format!("{}\n", summary))
+        }
+        // A failed rebuild is reported and the next change retries it
+        Err(e) => output.error(&e),
+    })?;
+    Ok(Outcome::Done)
+}
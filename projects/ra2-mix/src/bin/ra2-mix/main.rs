@@ -0,0 +1,300 @@
+//! Command line tool for RA2 MIX archives
+//!
+//! Exit codes: `0` on success, `1` when an operation fails, `2` on invalid arguments and `3` when `verify` finds
+//! problems in an archive.
+
+#[cfg(feature = "tui")]
+mod browse;
+mod commands;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use ra2_mix::{DatabasePolicy, MixError, SearchQuery, XccGame, ra2_crc};
+use std::{path::PathBuf, process::ExitCode};
+
+/// Read, build and check Red Alert 2 MIX archives
+#[derive(Debug, Parser)]
+#[command(name = "ra2-mix", version, about)]
+struct Cli {
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the entries of an archive
+    List {
+        /// The archive to read
+        mix: PathBuf,
+    },
+    /// Show the header, game and database of an archive
+    Info {
+        /// The archive to read
+        mix: PathBuf,
+    },
+    /// Extract entries of an archive to a folder
+    Extract {
+        /// The archive to read
+        mix: PathBuf,
+        /// The folder to write to
+        output: PathBuf,
+        /// The names or `0x` prefixed hexadecimal IDs of the entries to extract, every named entry if empty
+        #[arg(value_parser = parse_entry)]
+        entries: Vec<EntryArg>,
+    },
+    /// Create an archive from files and folders
+    Create {
+        /// The archive to write
+        mix: PathBuf,
+        /// The files to pack, folders add the files directly inside them
+        inputs: Vec<PathBuf>,
+        #[command(flatten)]
+        options: WriteOptions,
+    },
+    /// Add or replace files in an archive
+    Add {
+        /// The archive to update
+        mix: PathBuf,
+        /// The files to pack, folders add the files directly inside them
+        inputs: Vec<PathBuf>,
+    },
+    /// Remove entries from an archive
+    Remove {
+        /// The archive to update
+        mix: PathBuf,
+        /// The names or `0x` prefixed hexadecimal IDs of the entries to remove
+        #[arg(value_parser = parse_entry)]
+        entries: Vec<EntryArg>,
+    },
+    /// Pack every file of a folder into an archive, keeping its other entries
+    Patch {
+        /// The folder to read
+        input: PathBuf,
+        /// The archive to create or update
+        mix: PathBuf,
+    },
+    /// Export the header, game and entries of an archive with detected types and content hashes
+    Export {
+        /// The archive to read
+        mix: PathBuf,
+        /// The format to print, `--json` is ignored
+        #[arg(long, value_enum, default_value = "json")]
+        format: Format,
+    },
+    /// Check an archive for out-of-bounds, overlapping or duplicate entries and checksum mismatches
+    Verify {
+        /// The archive to check
+        mix: PathBuf,
+    },
+    /// Find entries across archives and folders by name, ID, content hash or content
+    Search {
+        #[command(flatten)]
+        query: QueryArgs,
+        /// The archives to search, and folders whose archives are searched recursively
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Report files that exist in several archives of a game folder, and which copy the game loads
+    Duplicates {
+        /// The game folder, whose archives are read recursively
+        folder: PathBuf,
+    },
+    /// Rebuild an archive whenever the files of a folder change, until interrupted
+    Watch {
+        /// The folder whose files are packed, including subfolders
+        input: PathBuf,
+        /// The archive to create or update
+        mix: PathBuf,
+        /// Milliseconds without changes to wait for before rebuilding
+        #[arg(long, default_value_t = 200)]
+        debounce: u64,
+    },
+    /// Browse an archive or a game folder interactively
+    #[cfg(feature = "tui")]
+    Browse {
+        /// The archive or folder to open
+        path: PathBuf,
+        /// The folder extracted entries are written to
+        #[arg(long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct WriteOptions {
+    /// The game the archive is for
+    #[arg(long, value_enum, default_value = "ra2")]
+    game: Game,
+    /// How the local mix database is written
+    #[arg(long, value_enum, default_value = "regenerate")]
+    database: Database,
+    /// Append a SHA-1 digest of the body
+    #[arg(long)]
+    checksum: bool,
+}
+
+#[derive(Debug, clap::Args)]
+#[group(required = true, multiple = false)]
+struct QueryArgs {
+    /// Names matching a pattern with `*` and `?` wildcards, ignoring case
+    #[arg(long)]
+    name: Option<String>,
+    /// Names matching a regular expression
+    #[arg(long)]
+    regex: Option<regex::Regex>,
+    /// A file name, or a `0x` prefixed hexadecimal file ID
+    #[arg(long, value_parser = parse_entry)]
+    id: Option<EntryArg>,
+    /// A content hash in hexadecimal, as printed by `search`
+    #[arg(long, value_parser = parse_hash)]
+    hash: Option<u32>,
+    /// Bytes in hexadecimal inside the data, such as `de ad be ef`
+    #[arg(long, value_parser = parse_bytes)]
+    bytes: Option<Vec<u8>>,
+    /// Text inside the data
+    #[arg(long)]
+    text: Option<String>,
+}
+
+impl From<QueryArgs> for SearchQuery {
+    fn from(query: QueryArgs) -> Self {
+        match query {
+            QueryArgs { name: Some(name), .. } => SearchQuery::Glob(name),
+            QueryArgs { regex: Some(regex), .. } => SearchQuery::Regex(regex),
+            QueryArgs { id: Some(id), .. } => SearchQuery::Id(id.id),
+            QueryArgs { hash: Some(hash), .. } => SearchQuery::Hash(hash),
+            QueryArgs { bytes: Some(bytes), .. } => SearchQuery::Bytes(bytes),
+            QueryArgs { text: Some(text), .. } => SearchQuery::Bytes(text.into_bytes()),
+            // The argument group requires one of the options
+            _ => unreachable!("no search query given"),
+        }
+    }
+}
+
+/// An entry given by name or by `0x` prefixed hexadecimal file ID
+#[derive(Debug, Clone)]
+struct EntryArg {
+    /// The argument as given
+    text: String,
+    /// The file ID
+    id: i32,
+}
+
+impl EntryArg {
+    /// Checks if the entry was given by file ID
+    fn is_id(&self) -> bool {
+        self.text.starts_with("0x") || self.text.starts_with("0X")
+    }
+}
+
+fn parse_entry(text: &str) -> Result<EntryArg, String> {
+    let id = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => {
+            u32::from_str_radix(hex, 16).map_err(|e| format!("`{}` is not a hexadecimal file ID: {}", text, e))? as i32
+        }
+        None => ra2_crc(text),
+    };
+    Ok(EntryArg { text: text.to_string(), id })
+}
+
+fn parse_hash(text: &str) -> Result<u32, String> {
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    u32::from_str_radix(hex, 16).map_err(|e| format!("`{}` is not a hexadecimal hash: {}", text, e))
+}
+
+fn parse_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let hex: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if hex.is_empty() || !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(format!("`{}` is not a whole number of hexadecimal bytes", text));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| format!("`{}` is not hexadecimal: {}", text, e)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Game {
+    Td,
+    Ra,
+    Ts,
+    Ra2,
+    Yr,
+}
+
+impl From<Game> for XccGame {
+    fn from(game: Game) -> Self {
+        match game {
+            Game::Td => XccGame::TD,
+            Game::Ra => XccGame::RA,
+            Game::Ts => XccGame::TS,
+            Game::Ra2 => XccGame::RA2,
+            Game::Yr => XccGame::RA2_YR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Database {
+    Regenerate,
+    Merge,
+    Omit,
+}
+
+impl From<Database> for DatabasePolicy {
+    fn from(database: Database) -> Self {
+        match database {
+            Database::Regenerate => DatabasePolicy::Regenerate,
+            Database::Merge => DatabasePolicy::Merge,
+            Database::Omit => DatabasePolicy::Omit,
+        }
+    }
+}
+
+/// How a command ended
+enum Outcome {
+    /// The command succeeded
+    Done,
+    /// The command ran but found problems
+    Problems,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let output = commands::Output { json: cli.json };
+    match run(cli.command, &output) {
+        Ok(Outcome::Done) => ExitCode::SUCCESS,
+        Ok(Outcome::Problems) => ExitCode::from(3),
+        Err(e) => {
+            output.error(&e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn run(command: Command, output: &commands::Output) -> Result<Outcome, MixError> {
+    match command {
+        Command::List { mix } => commands::list(&mix, output),
+        Command::Info { mix } => commands::info(&mix, output),
+        Command::Extract { mix, output: folder, entries } => commands::extract(&mix, &folder, &entries, output),
+        Command::Create { mix, inputs, options } => commands::create(&mix, &inputs, &options, output),
+        Command::Add { mix, inputs } => commands::add(&mix, &inputs, output),
+        Command::Remove { mix, entries } => commands::remove(&mix, &entries, output),
+        Command::Patch { input, mix } => commands::patch(&input, &mix, output),
+        Command::Export { mix, format } => commands::export(&mix, format),
+        Command::Verify { mix } => commands::verify(&mix, output),
+        Command::Search { query, inputs } => commands::search(&query.into(), &inputs, output),
+        Command::Duplicates { folder } => commands::duplicates(&folder, output),
+        Command::Watch { input, mix, debounce } => commands::watch(&input, &mix, debounce, output),
+        #[cfg(feature = "tui")]
+        Command::Browse { path, output: folder } => browse::run(&path, &folder).map(|_| Outcome::Done),
+    }
+}
@@ -0,0 +1,447 @@
+//! Persistent index of every entry across a game installation
+//!
+//! A [`Catalog`] records where each file of a game folder lives, down to archives nested in archives, so lookups do
+//! not have to open every archive again. It is saved to a compact binary cache and updated incrementally: archives
+//! whose size and modification time did not change are not read again.
+
+use crate::{
+    EntryKind, MixError, MixIndex, SearchQuery,
+    checksum::{content_hash, ra2_crc},
+    xcc_package::reader::{database_range, resolve_names},
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The first bytes of a catalog cache
+const CATALOG_MAGIC: &[u8; 8] = b"RA2CATLG";
+/// The version of the cache layout, caches of other versions are rebuilt
+const CATALOG_VERSION: u32 = 1;
+/// How deep nested archives are followed, deeper archives are recorded as plain entries
+const MAX_NESTING: usize = 8;
+
+/// An entry of an archive in a [`Catalog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct CatalogEntry {
+    /// The file ID
+    pub id: i32,
+    /// The file name, if the archive database or the global database knows it
+    pub name: Option<String>,
+    /// The nested archives that contain the entry inside the archive file, outermost first, empty for entries of the
+    /// archive file itself
+    pub nested: Vec<String>,
+    /// The offset of the data from the start of the archive file
+    pub offset: u64,
+    /// The size of the data in bytes
+    pub size: u64,
+    /// The [`content_hash`] of the data
+    pub hash: u32,
+}
+
+/// An archive file of a [`Catalog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct CatalogArchive {
+    /// The path of the archive, relative to the catalog root
+    pub path: PathBuf,
+    /// The modification time of the file when it was indexed
+    pub modified: SystemTime,
+    /// The size of the file when it was indexed
+    pub size: u64,
+    /// Every entry of the archive and of the archives nested in it
+    pub entries: Vec<CatalogEntry>,
+    /// Why the archive could not be read, its entries are empty then
+    pub error: Option<String>,
+}
+
+/// An entry found in a [`Catalog`], with the archive that holds it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatalogHit<'a> {
+    /// The archive file that holds the entry
+    pub archive: &'a CatalogArchive,
+    /// The entry
+    pub entry: &'a CatalogEntry,
+}
+
+impl CatalogHit<'_> {
+    /// The archive file followed by the nested archives that contain the entry
+    ///
+    /// # Examples
+    /// `["MIXES/expandmd01.mix", "maps.mix"]` for an entry of `maps.mix`, which is packed into `expandmd01.mix`.
+    pub fn chain(&self) -> Vec<String> {
+        let file = self.archive.path.to_string_lossy().replace('\\', "/");
+        std::iter::once(file).chain(self.entry.nested.iter().cloned()).collect()
+    }
+}
+
+/// What [`Catalog::update`] did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CatalogUpdate {
+    /// Archives that were not in the catalog
+    pub added: usize,
+    /// Archives whose size or modification time changed
+    pub changed: usize,
+    /// Archives that no longer exist
+    pub removed: usize,
+    /// Archives kept from the catalog without reading them
+    pub unchanged: usize,
+    /// Subfolders and archives that could not be read, they are left out
+    pub unreadable: usize,
+}
+
+/// Index of every entry across the archives of a game folder
+///
+/// # Examples
+/// ```no_run
+/// use ra2_mix::Catalog;
+/// use std::path::Path;
+///
+/// let game = Path::new("C:/Westwood/RA2");
+/// let (catalog, _) = Catalog::open(game, &game.join("ra2-mix.catalog")).unwrap();
+/// for hit in catalog.lookup("gapowr.shp") {
+///     println!("{} at {}", hit.chain().join(" > "), hit.entry.offset);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    root: PathBuf,
+    archives: Vec<CatalogArchive>,
+    /// The positions of the entries with each file ID, as archive and entry indices
+    ids: HashMap<i32, Vec<(usize, usize)>>,
+}
+
+impl Catalog {
+    /// Creates an empty catalog of a game folder, filled by [`Catalog::update`]
+    pub fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf(), ..Default::default() }
+    }
+    /// Indexes every archive below a game folder
+    pub fn build(root: &Path) -> Result<Self, MixError> {
+        let mut catalog = Catalog::new(root);
+        catalog.update()?;
+        Ok(catalog)
+    }
+    /// Loads the cache of a game folder, brings it up to date and saves it
+    ///
+    /// A missing or unreadable cache, or one made for another folder, is rebuilt from scratch.
+    ///
+    /// # Arguments
+    /// * `root` - The game folder
+    /// * `cache` - The catalog file
+    pub fn open(root: &Path, cache: &Path) -> Result<(Self, CatalogUpdate), MixError> {
+        let mut catalog = match Catalog::load(cache) {
+            Ok(catalog) if catalog.root == root => catalog,
+            _ => Catalog::new(root),
+        };
+        let update = catalog.update()?;
+        catalog.save(cache)?;
+        Ok((catalog, update))
+    }
+    /// Reads an archive again if its size or modification time changed, and adds and removes archives to match the
+    /// folder
+    pub fn update(&mut self) -> Result<CatalogUpdate, MixError> {
+        let mut files = Vec::new();
+        let unreadable = find_archives(&self.root, &mut files)?;
+        files.sort();
+
+        let mut previous: HashMap<PathBuf, CatalogArchive> =
+            std::mem::take(&mut self.archives).into_iter().map(|archive| (archive.path.clone(), archive)).collect();
+        let mut update = CatalogUpdate { unreadable, ..Default::default() };
+        for file in files {
+            // An archive deleted since the folder was listed is left out, the catalog keeps every other archive
+            let metadata = match std::fs::metadata(&file) {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    update.unreadable += 1;
+                    continue;
+                }
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let path = file.strip_prefix(&self.root).unwrap_or(&file).to_path_buf();
+            let archive = match previous.remove(&path) {
+                Some(archive) if archive.size == metadata.len() && archive.modified == modified => {
+                    update.unchanged += 1;
+                    archive
+                }
+                Some(_) => {
+                    update.changed += 1;
+                    index_archive(&file, path, modified, metadata.len())
+                }
+                None => {
+                    update.added += 1;
+                    index_archive(&file, path, modified, metadata.len())
+                }
+            };
+            self.archives.push(archive);
+        }
+        update.removed = previous.len();
+        self.rebuild_ids();
+        Ok(update)
+    }
+    /// The game folder
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// The archive files, sorted by path
+    pub fn archives(&self) -> &[CatalogArchive] {
+        &self.archives
+    }
+    /// Every entry of every archive
+    pub fn entries(&self) -> impl Iterator<Item = CatalogHit<'_>> {
+        self.archives.iter().flat_map(|archive| archive.entries.iter().map(move |entry| CatalogHit { archive, entry }))
+    }
+    /// Finds the entries with a file name, case-insensitively
+    pub fn lookup(&self, name: &str) -> Vec<CatalogHit<'_>> {
+        self.lookup_id(ra2_crc(name))
+    }
+    /// Finds the entries with a file ID
+    pub fn lookup_id(&self, id: i32) -> Vec<CatalogHit<'_>> {
+        let positions = self.ids.get(&id).map(Vec::as_slice).unwrap_or_default();
+        positions.iter().map(|&(archive, entry)| self.hit(archive, entry)).collect()
+    }
+    /// Finds the entries with the same content
+    ///
+    /// # Arguments
+    /// * `hash` - The [`content_hash`] of the content
+    pub fn lookup_hash(&self, hash: u32) -> Vec<CatalogHit<'_>> {
+        self.entries().filter(|hit| hit.entry.hash == hash).collect()
+    }
+    /// Finds the entries matching a query, reading the data from the archive files only for content queries
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<CatalogHit<'_>>, MixError> {
+        let mut hits = Vec::new();
+        for archive in &self.archives {
+            let mut file: Option<File> = None;
+            for entry in &archive.entries {
+                let is_match = match query.matches_listing(entry.id, entry.name.as_deref(), entry.hash) {
+                    Some(is_match) => is_match,
+                    None => {
+                        let file = match &mut file {
+                            Some(file) => file,
+                            None => file.insert(File::open(self.root.join(&archive.path))?),
+                        };
+                        file.seek(SeekFrom::Start(entry.offset))?;
+                        let mut data = Vec::with_capacity(entry.size as usize);
+                        file.take(entry.size).read_to_end(&mut data)?;
+                        query.is_match(entry.id, entry.name.as_deref(), &data)
+                    }
+                };
+                if is_match {
+                    hits.push(CatalogHit { archive, entry });
+                }
+            }
+        }
+        Ok(hits)
+    }
+    fn hit(&self, archive: usize, entry: usize) -> CatalogHit<'_> {
+        let archive = &self.archives[archive];
+        CatalogHit { archive, entry: &archive.entries[entry] }
+    }
+    fn rebuild_ids(&mut self) {
+        self.ids.clear();
+        for (a, archive) in self.archives.iter().enumerate() {
+            for (e, entry) in archive.entries.iter().enumerate() {
+                self.ids.entry(entry.id).or_default().push((a, e));
+            }
+        }
+    }
+    /// Reads a catalog saved by [`Catalog::save`]
+    pub fn load(cache: &Path) -> Result<Self, MixError> {
+        let context = |e: MixError| e.context(format!("Failed to load `{}`", cache.display()));
+        let data = std::fs::read(cache).map_err(|e| context(e.into()))?;
+        Catalog::decode(&data).map_err(context)
+    }
+    /// Writes the catalog to a file
+    pub fn save(&self, cache: &Path) -> Result<(), MixError> {
+        let context = |e: MixError| e.context(format!("Failed to save `{}`", cache.display()));
+        std::fs::write(cache, self.encode()).map_err(|e| context(e.into()))
+    }
+    /// Decodes a catalog from the bytes of [`Catalog::encode`]
+    pub fn decode(data: &[u8]) -> Result<Self, MixError> {
+        if !data.starts_with(CATALOG_MAGIC) {
+            return Err(MixError::InvalidFormat("Not a catalog".to_string()));
+        }
+        let mut cursor = Cursor::new(&data[CATALOG_MAGIC.len()..]);
+        let version = cursor.read_u32::<LittleEndian>()?;
+        if version != CATALOG_VERSION {
+            return Err(MixError::InvalidFormat(format!("Unsupported catalog version {}", version)));
+        }
+        let root = PathBuf::from(read_string(&mut cursor)?);
+        let mut archives = Vec::new();
+        for _ in 0..cursor.read_u32::<LittleEndian>()? {
+            let path = PathBuf::from(read_string(&mut cursor)?);
+            let seconds = cursor.read_u64::<LittleEndian>()?;
+            let nanos = cursor.read_u32::<LittleEndian>()?;
+            let modified = SystemTime::UNIX_EPOCH + Duration::new(seconds, nanos);
+            let size = cursor.read_u64::<LittleEndian>()?;
+            let error = read_optional_string(&mut cursor)?;
+            let mut entries = Vec::new();
+            for _ in 0..cursor.read_u32::<LittleEndian>()? {
+                let id = cursor.read_i32::<LittleEndian>()?;
+                let name = read_optional_string(&mut cursor)?;
+                let mut nested = Vec::new();
+                for _ in 0..cursor.read_u8()? {
+                    nested.push(read_string(&mut cursor)?);
+                }
+                let offset = cursor.read_u64::<LittleEndian>()?;
+                let size = cursor.read_u64::<LittleEndian>()?;
+                let hash = cursor.read_u32::<LittleEndian>()?;
+                entries.push(CatalogEntry { id, name, nested, offset, size, hash });
+            }
+            archives.push(CatalogArchive { path, modified, size, entries, error });
+        }
+        let mut catalog = Catalog { root, archives, ids: HashMap::new() };
+        catalog.rebuild_ids();
+        Ok(catalog)
+    }
+    /// Encodes the catalog, with little-endian numbers and length-prefixed UTF-8 strings
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = CATALOG_MAGIC.to_vec();
+        data.write_u32::<LittleEndian>(CATALOG_VERSION).unwrap();
+        write_string(&mut data, &self.root.to_string_lossy());
+        data.write_u32::<LittleEndian>(self.archives.len() as u32).unwrap();
+        for archive in &self.archives {
+            write_string(&mut data, &archive.path.to_string_lossy());
+            let modified = archive.modified.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+            data.write_u64::<LittleEndian>(modified.as_secs()).unwrap();
+            data.write_u32::<LittleEndian>(modified.subsec_nanos()).unwrap();
+            data.write_u64::<LittleEndian>(archive.size).unwrap();
+            write_optional_string(&mut data, archive.error.as_deref());
+            data.write_u32::<LittleEndian>(archive.entries.len() as u32).unwrap();
+            for entry in &archive.entries {
+                data.write_i32::<LittleEndian>(entry.id).unwrap();
+                write_optional_string(&mut data, entry.name.as_deref());
+                data.write_u8(entry.nested.len() as u8).unwrap();
+                for name in &entry.nested {
+                    write_string(&mut data, name);
+                }
+                data.write_u64::<LittleEndian>(entry.offset).unwrap();
+                data.write_u64::<LittleEndian>(entry.size).unwrap();
+                data.write_u32::<LittleEndian>(entry.hash).unwrap();
+            }
+        }
+        data
+    }
+}
+
+/// Lists the archive files below a folder, by extension
+///
+/// Links to folders are not followed, so a link back to a parent cannot recurse forever, links to files are.
+///
+/// # Returns
+/// The number of subfolders that could not be read and were skipped, or an error if `folder` cannot be read
+pub(crate) fn find_archives(folder: &Path, files: &mut Vec<PathBuf>) -> Result<usize, MixError> {
+    let mut unreadable = 0;
+    for entry in std::fs::read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+        // Unlike `Path::is_dir`, the file type of an entry describes the link itself
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            unreadable += find_archives(&path, files).unwrap_or(1);
+        }
+        else if (file_type.is_file() || (file_type.is_symlink() && path.is_file()))
+            && EntryKind::detect(path.file_name().and_then(|name| name.to_str()), &[]) == EntryKind::Mix
+        {
+            files.push(path);
+        }
+    }
+    Ok(unreadable)
+}
+
+/// Reads and indexes an archive file, recording the error if it is not a valid archive
+fn index_archive(file: &Path, path: PathBuf, modified: SystemTime, size: u64) -> CatalogArchive {
+    let mut entries = Vec::new();
+    let data = std::fs::read(file).map_err(MixError::from);
+    let result = data.and_then(|data| {
+        let mut walked = Vec::new();
+        walk_entries(&data, 0, &mut Vec::new(), true, &mut walked)?;
+        entries.extend(walked.into_iter().map(|(entry, _)| entry));
+        Ok(())
+    });
+    CatalogArchive { path, modified, size, entries, error: result.err().map(|e| e.to_string()) }
+}
+
+/// Lists the entries of an archive and of the archives nested in it, with their data
+///
+/// # Arguments
+/// * `data` - The archive
+/// * `base` - The offset of the archive in the archive file
+/// * `nested` - The nested archives that lead to this one
+/// * `hashed` - Whether to compute the [`content_hash`] of every entry, the hash is left 0 otherwise
+/// * `entries` - Receives the entries
+pub(crate) fn walk_entries<'a>(
+    data: &'a [u8],
+    base: u64,
+    nested: &mut Vec<String>,
+    hashed: bool,
+    entries: &mut Vec<(CatalogEntry, &'a [u8])>,
+) -> Result<(), MixError> {
+    let index = MixIndex::parse(data)?;
+    let body_size = (data.len() as u64).saturating_sub(index.body_offset);
+    let body = |start: u64, size: u64| {
+        let start = (index.body_offset + start) as usize;
+        &data[start..start + size as usize]
+    };
+    let database = database_range(&index, body_size, &mut Vec::new()).map(|(start, size)| body(start, size));
+    let resolved = resolve_names(&index.entries, database, &mut Vec::new())?;
+    for entry in &index.entries {
+        // Protected archives add entries outside the body, the game never reads them
+        let (start, size) = match entry.range(body_size) {
+            Ok(range) => range,
+            Err(_) => continue,
+        };
+        let bytes = body(start, size);
+        let name = resolved.names.get(&entry.id).cloned();
+        let offset = base + index.body_offset + start;
+        let label = name.clone().unwrap_or_else(|| format!("{:08X}", entry.id));
+        let is_mix = EntryKind::detect(name.as_deref(), bytes) == EntryKind::Mix;
+        let hash = if hashed { content_hash(bytes) } else { 0 };
+        entries.push((CatalogEntry { id: entry.id, name, nested: nested.clone(), offset, size, hash }, bytes));
+        if is_mix && nested.len() < MAX_NESTING {
+            nested.push(label);
+            // Entries that only look like archives are kept as plain entries
+            let mut inner = Vec::new();
+            if walk_entries(bytes, offset, nested, hashed, &mut inner).is_ok() {
+                entries.append(&mut inner);
+            }
+            nested.pop();
+        }
+    }
+    Ok(())
+}
+
+fn write_string(data: &mut Vec<u8>, value: &str) {
+    data.write_u32::<LittleEndian>(value.len() as u32).unwrap();
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn write_optional_string(data: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            data.write_u8(1).unwrap();
+            write_string(data, value);
+        }
+        None => data.write_u8(0).unwrap(),
+    }
+}
+
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, MixError> {
+    let length = cursor.read_u32::<LittleEndian>()? as usize;
+    MixError::check_bounds(cursor.position() as usize, length, cursor.get_ref().len())?;
+    let mut bytes = vec![0; length];
+    cursor.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| MixError::InvalidFormat("Invalid UTF-8 in catalog".to_string()))
+}
+
+fn read_optional_string(cursor: &mut Cursor<&[u8]>) -> Result<Option<String>, MixError> {
+    match cursor.read_u8()? {
+        0 => Ok(None),
+        _ => read_string(cursor).map(Some),
+    }
+}
@@ -45,4 +45,15 @@ pub fn ra2_crc(filename: &str) -> i32 {
     
     // Convert to signed 32-bit integer for proper sorting in mix file
     crc as i32
-}
\ No newline at end of file
+}
+
+/// Calculates the content hash of a file, used to compare entries between archives
+///
+/// # Arguments
+/// * `data` - The file data
+///
+/// # Returns
+/// The CRC32 checksum of the data
+pub fn content_hash(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
@@ -17,6 +17,20 @@ pub const SIZE_OF_DATA_SIZE: usize = 4;
 /// Size of encrypted key in the header
 pub const SIZE_OF_ENCRYPTED_KEY: usize = 80;
 
+/// Header flag of MIX files with a SHA-1 digest of the body appended
+pub const FLAG_CHECKSUM: u32 = 0x10000;
+/// Size of the SHA-1 digest appended to checksummed MIX files
+pub const CHECKSUM_SIZE: usize = 20;
+/// Header flag of MIX files with an encrypted header and index
+pub const FLAG_ENCRYPTED: u32 = 0x20000;
+
+/// Maximum number of files a MIX header can describe
+pub const MAX_FILE_COUNT: usize = u16::MAX as usize;
+/// Maximum size of the body data a MIX header can describe
+///
+/// Index entries store offsets and sizes as signed 32-bit numbers, so larger bodies could not be read back.
+pub const MAX_BODY_SIZE: u64 = i32::MAX as u64;
+
 /// Name of the local mix database file
 pub const MIX_DB_FILENAME: &str = "local mix database.dat";
 
@@ -32,8 +46,9 @@ pub const XCC_ID_BYTES: &[u8] = b"XCC by Olaf van der Spek\x1a\x04\x17\x27\x10\x
 
 /// Enum representing different games that use XCC format
 #[allow(non_camel_case_types)]
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(any(feature = "manifest", feature = "serde_json"), derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize))]
 pub enum XccGame {
     /// Tiberian Dawn
     TD = 0,
@@ -46,6 +61,7 @@ pub enum XccGame {
     /// Dune 2000
     DUNE2000 = 4,
     /// Red Alert 2
+    #[default]
     RA2 = 5,
     /// Red Alert 2: Yuri's Revenge
     RA2_YR = 6,
@@ -71,8 +87,33 @@ pub enum XccGame {
     UNKNOWN = 16,
 }
 
-impl Default for XccGame {
-    fn default() -> Self {
-        Self::RA2
+impl From<u32> for XccGame {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::TD,
+            1 => Self::RA,
+            2 => Self::TS,
+            3 => Self::DUNE2,
+            4 => Self::DUNE2000,
+            5 => Self::RA2,
+            6 => Self::RA2_YR,
+            7 => Self::RG,
+            8 => Self::GR,
+            9 => Self::GR_ZH,
+            10 => Self::EBFD,
+            11 => Self::NOX,
+            12 => Self::BFME,
+            13 => Self::BFME2,
+            14 => Self::TW,
+            15 => Self::TS_FS,
+            _ => Self::UNKNOWN,
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Files that only ship with Red Alert 2: Yuri's Revenge, used to guess the game of archives without a database
+pub const RA2_YR_SIGNATURE_FILES: &[&str] =
+    &["rulesmd.ini", "artmd.ini", "aimd.ini", "ra2md.csf", "langmd.mix", "localmd.mix", "conqmd.mix", "genermd.mix"];
+
+/// Files that only ship with Red Alert 2, used to guess the game of archives without a database
+pub const RA2_SIGNATURE_FILES: &[&str] = &["ra2.csf", "language.mix", "urban.mix", "isourb.mix"];
\ No newline at end of file
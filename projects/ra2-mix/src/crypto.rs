@@ -1,6 +1,6 @@
 //! Cryptography utilities for RA2 MIX files
 
-use crate::{MixError, constants::*};
+use crate::{MixError, constants::*, errors::CryptoStage};
 use blowfish::{
     Blowfish,
     cipher::{BlockDecrypt, KeyInit, generic_array},
@@ -25,19 +25,13 @@ pub fn decrypt_blowfish_key(encrypted_blowfish_key: &[u8]) -> Result<Vec<u8>, Mi
         b"681994811107118991598552881669230523074742337494683459234572860554038768387821901289207730765589",
         10,
     )
-    .ok_or(MixError::CryptoError("Failed to parse public modulus".to_string()))?;
-    
-    println!("HEX KEY: {:?}", public_modulus.to_bytes_le());
-    
-    let _ = BigUint::from_bytes_le(public_modulus.to_bytes_le().as_slice());
-
-    if encrypted_blowfish_key.len() < SIZE_OF_ENCRYPTED_KEY {
-        return Err(MixError::CryptoError("Buffer is not long enough".to_string()));
-    }
+    .ok_or(MixError::Crypto { stage: CryptoStage::PublicKey, source: None })?;
+
+    MixError::check_bounds(0, SIZE_OF_ENCRYPTED_KEY, encrypted_blowfish_key.len())?;
 
     // Create RSA public key
     let public_key = RsaPublicKey::new(public_modulus, BigUint::from(PUBLIC_EXPONENT))
-        .map_err(|e| MixError::CryptoError(format!("Failed to create RSA key: {}", e)))?;
+        .map_err(|e| MixError::Crypto { stage: CryptoStage::PublicKey, source: Some(Box::new(e)) })?;
 
     let mut decrypted_blowfish_key = Vec::new();
 
@@ -51,8 +45,8 @@ pub fn decrypt_blowfish_key(encrypted_blowfish_key: &[u8]) -> Result<Vec<u8>, Mi
 
         // Perform RSA decryption (actually encryption with public key in this case)
         let decrypted_int = public_key
-            .encrypt(&mut rand::thread_rng(), Pkcs1v15Encrypt::default(), &block_int.to_bytes_le())
-            .map_err(|e| MixError::CryptoError(format!("RSA decryption failed: {}", e)))?;
+            .encrypt(&mut rand::thread_rng(), Pkcs1v15Encrypt, &block_int.to_bytes_le())
+            .map_err(|e| MixError::Crypto { stage: CryptoStage::KeyDecryption, source: Some(Box::new(e)) })?;
 
         // Remove trailing zeros
         let mut decrypted = decrypted_int.to_vec();
@@ -77,9 +71,10 @@ pub fn decrypt_blowfish_key(encrypted_blowfish_key: &[u8]) -> Result<Vec<u8>, Mi
 pub fn decrypt_mix_header(mix_data: &[u8], key: &[u8]) -> Result<(u16, u32, Vec<u8>), MixError> {
     // Create Blowfish cipher with LittleEndian byte order
     let cipher = Blowfish::<LittleEndian>::new_from_slice(key)
-        .map_err(|e| MixError::CryptoError(format!("Failed to create Blowfish cipher: {}", e)))?;
+        .map_err(|_| MixError::Crypto { stage: CryptoStage::HeaderDecryption, source: None })?;
 
     let header_start = SIZE_OF_FLAGS + SIZE_OF_ENCRYPTED_KEY;
+    MixError::check_bounds(header_start, BLOCK_SIZE, mix_data.len())?;
 
     // Decrypt the first block
     let mut decrypted_block = [0u8; BLOCK_SIZE];
@@ -102,6 +97,7 @@ pub fn decrypt_mix_header(mix_data: &[u8], key: &[u8]) -> Result<(u16, u32, Vec<
     let (decrypt_size, padding_size) = get_decryption_block_sizing(file_count);
 
     // Decrypt the rest of the index data
+    MixError::check_bounds(header_start + BLOCK_SIZE, decrypt_size, mix_data.len())?;
     let encrypted_data = &mix_data[header_start + BLOCK_SIZE..header_start + BLOCK_SIZE + decrypt_size];
     let mut data_decrypted = Vec::with_capacity(decrypt_size);
 
@@ -152,7 +148,7 @@ pub fn decrypt_mix_header(mix_data: &[u8], key: &[u8]) -> Result<(u16, u32, Vec<
 /// A tuple containing the decrypt size and padding size
 pub fn get_decryption_block_sizing(file_count: u16) -> (usize, usize) {
     let index_len = file_count as usize * FILE_ENTRY_SIZE;
-    let remaining_index_len = index_len - SIZE_OF_FILE_COUNT;
+    let remaining_index_len = index_len.saturating_sub(SIZE_OF_FILE_COUNT);
     let padding_size = BLOCK_SIZE - (remaining_index_len % BLOCK_SIZE);
     let decrypt_size = remaining_index_len + padding_size;
 
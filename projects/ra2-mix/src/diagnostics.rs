@@ -0,0 +1,78 @@
+//! Diagnostics reported while reading RA2 MIX files
+
+use std::fmt::{Display, Formatter};
+
+/// A recoverable problem found while reading an archive
+///
+/// Warnings never stop an operation; they are returned alongside its result so applications can show or ignore
+/// them. With the `log` feature, every warning is also emitted through the `log` facade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixWarning {
+    /// The archive has no local mix database, so entry names could not be resolved
+    MissingDatabase,
+    /// The local mix database is too short or has no XCC signature, so it was ignored like a missing one
+    InvalidDatabase,
+    /// Some entries have no name in the local mix database and were kept by ID
+    UnnamedEntries {
+        /// The number of unnamed entries
+        count: usize,
+    },
+    /// An entry points outside the archive body and was read as empty
+    ///
+    /// Protected archives add such entries, and point their local mix database outside the body, to stop editors
+    /// from opening them while the game never reads them. Loading goes on, reading the entry alone fails with
+    /// [`MixError::InvalidIndex`](crate::MixError::InvalidIndex).
+    EntryOutOfBounds {
+        /// The file ID of the entry
+        id: i32,
+        /// The offset of the entry in the body
+        offset: i64,
+        /// The size of the entry
+        size: i64,
+    },
+}
+
+impl MixWarning {
+    /// A stable code identifying the kind of warning
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::MixWarning;
+    ///
+    /// assert_eq!(MixWarning::MissingDatabase.code(), "missing-database");
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            MixWarning::MissingDatabase => "missing-database",
+            MixWarning::InvalidDatabase => "invalid-database",
+            MixWarning::UnnamedEntries { .. } => "unnamed-entries",
+            MixWarning::EntryOutOfBounds { .. } => "entry-out-of-bounds",
+        }
+    }
+}
+
+impl Display for MixWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MixWarning::MissingDatabase => {
+                write!(f, "No local mix database found, entry names are unknown")
+            }
+            MixWarning::InvalidDatabase => {
+                write!(f, "The local mix database is malformed and was ignored, entry names are unknown")
+            }
+            MixWarning::UnnamedEntries { count } => {
+                write!(f, "{} entries have no name in the local mix database", count)
+            }
+            MixWarning::EntryOutOfBounds { id, offset, size } => {
+                write!(f, "Entry {:08X} at offset {} with size {} lies outside the body", id, offset, size)
+            }
+        }
+    }
+}
+
+/// Records a warning, and emits it through the `log` facade when the feature is enabled
+pub(crate) fn report(warnings: &mut Vec<MixWarning>, warning: MixWarning) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "ra2_mix", "[{}] {}", warning.code(), warning);
+    warnings.push(warning);
+}
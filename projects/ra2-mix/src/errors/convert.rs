@@ -4,4 +4,14 @@ impl From<std::io::Error> for MixError {
     fn from(error: std::io::Error) -> Self {
         Self::IoError(error)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "watch")]
+impl From<notify::Error> for MixError {
+    fn from(error: notify::Error) -> Self {
+        match error.kind {
+            notify::ErrorKind::Io(error) => Self::IoError(error),
+            _ => Self::IoError(std::io::Error::other(error)),
+        }
+    }
+}
@@ -1,9 +1,81 @@
 use super::*;
 
-impl Error for MixError { }
+impl Error for MixError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MixError::IoError(e) => Some(e),
+            MixError::Crypto { source: Some(e), .. } => Some(e.as_ref()),
+            MixError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl Display for MixError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self { MixError::UnknownError => { write!(f, "UnknownError") } }
+        match self {
+            MixError::IoError(e) => {
+                write!(f, "IO error: {}", e)
+            }
+            MixError::Truncated { offset, needed, available } => {
+                write!(f, "Truncated data: {} bytes needed at offset {}, but only {} available", needed, offset, available)
+            }
+            MixError::InvalidIndex { id, offset, size, body_size } => {
+                write!(
+                    f,
+                    "Invalid index: entry {:08X} at offset {} with size {} lies outside the {} byte body",
+                    id, offset, size, body_size
+                )
+            }
+            MixError::Crypto { stage, source: Some(e) } => {
+                write!(f, "Crypto error: {} failed: {}", stage, e)
+            }
+            MixError::Crypto { stage, source: None } => {
+                write!(f, "Crypto error: {} failed", stage)
+            }
+            MixError::InvalidFormat(e) => {
+                write!(f, "Invalid file format: {}", e)
+            }
+            MixError::FileNotFound(e) => {
+                write!(f, "File not found: {}", e.display())
+            }
+            MixError::EntryNotFound(id) => {
+                write!(f, "Entry not found: no entry with ID {:08X}", id)
+            }
+            MixError::NameCollision { id, first, second } => {
+                write!(f, "Name collision: `{}` and `{}` share the file ID {:08X}", first, second, id)
+            }
+            MixError::EntryConflict { id, name: Some(name) } => {
+                write!(f, "Entry conflict: `{}` ({:08X}) differs between packages", name, id)
+            }
+            MixError::EntryConflict { id, name: None } => {
+                write!(f, "Entry conflict: {:08X} differs between packages", id)
+            }
+            MixError::TooManyFiles { count, limit } => {
+                write!(f, "Too many files: {} files exceed the limit of {}", count, limit)
+            }
+            MixError::BodyTooLarge { size, limit } => {
+                write!(f, "Body too large: {} bytes exceed the limit of {} bytes", size, limit)
+            }
+            MixError::Cancelled => {
+                write!(f, "Operation cancelled")
+            }
+            MixError::Unsupported(e) => {
+                write!(f, "Unsupported: {}", e)
+            }
+            MixError::Context { context, source } => {
+                write!(f, "{}: {}", context, source)
+            }
+        }
+    }
+}
+
+impl Display for CryptoStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoStage::PublicKey => write!(f, "building the RSA public key"),
+            CryptoStage::KeyDecryption => write!(f, "decrypting the Blowfish key"),
+            CryptoStage::HeaderDecryption => write!(f, "decrypting the header"),
+        }
     }
-}
\ No newline at end of file
+}
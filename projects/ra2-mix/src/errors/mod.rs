@@ -1,8 +1,11 @@
 //! Error types for RA2 MIX file operations
 
 mod convert;
+mod display;
 use std::{
+    error::Error,
     fmt::{Display, Formatter},
+    path::PathBuf,
 };
 
 /// Result type for RA2 MIX file operations
@@ -14,31 +17,146 @@ pub enum MixError {
     /// IO error
     IoError(std::io::Error),
 
-    /// Crypto error
-    CryptoError(String),
+    /// The data ends before a structure that starts at `offset`
+    Truncated {
+        /// The byte offset of the structure
+        offset: u64,
+        /// The number of bytes the structure needs
+        needed: u64,
+        /// The number of bytes left from the offset
+        available: u64,
+    },
+
+    /// An index entry points outside the archive body
+    ///
+    /// Only reading that entry fails, loading a whole archive reads it as empty and reports
+    /// [`MixWarning::EntryOutOfBounds`](crate::MixWarning::EntryOutOfBounds) instead.
+    InvalidIndex {
+        /// The file ID of the entry
+        id: i32,
+        /// The offset of the entry in the body
+        offset: i64,
+        /// The size of the entry
+        size: i64,
+        /// The size of the archive body
+        body_size: u64,
+    },
+
+    /// Decrypting an encrypted header failed
+    Crypto {
+        /// The step that failed
+        stage: CryptoStage,
+        /// The underlying cryptography error, if any
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
 
     /// Invalid file format
     InvalidFormat(String),
 
     /// Missing file
-    FileNotFound(String),
+    FileNotFound(PathBuf),
+
+    /// The archive has no entry with the file ID
+    EntryNotFound(i32),
+
+    /// Two different file names hash to the same file ID
+    NameCollision {
+        /// The file ID shared by both names
+        id: i32,
+        /// The first file name
+        first: String,
+        /// The second file name
+        second: String,
+    },
+
+    /// An entry exists in several merged packages with different contents
+    EntryConflict {
+        /// The file ID of the entry
+        id: i32,
+        /// The file name of the entry, if known
+        name: Option<String>,
+    },
+
+    /// The archive holds more files than the header can describe
+    TooManyFiles {
+        /// The number of files, including the local mix database
+        count: usize,
+        /// The maximum number of files
+        limit: usize,
+    },
+
+    /// The archive body is larger than the header can describe
+    BodyTooLarge {
+        /// The size of the body data in bytes
+        size: u64,
+        /// The maximum size of the body data in bytes
+        limit: u64,
+    },
+
+    /// The operation was stopped through its cancellation token, never wrapped in [`MixError::Context`]
+    Cancelled,
+
+    /// The requested feature cannot be written or read by this library
+    Unsupported(String),
+
+    /// An error with a description of what was being done, such as the file being read
+    Context {
+        /// What was being done when the error happened
+        context: String,
+        /// The error that happened
+        source: Box<MixError>,
+    },
 }
 
-impl Display for MixError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+/// Step of the header decryption that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoStage {
+    /// Building the RSA public key
+    PublicKey,
+    /// Decrypting the Blowfish key with RSA
+    KeyDecryption,
+    /// Decrypting the header and index with Blowfish
+    HeaderDecryption,
+}
+
+impl MixError {
+    /// Wraps the error with a description of what was being done
+    ///
+    /// [`MixError::Cancelled`] is returned as is, so callers can match it without going through [`MixError::root`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::MixError;
+    ///
+    /// let error = MixError::InvalidFormat("bad header".to_string()).context("reading `ra2.mix`");
+    /// assert!(matches!(error.root(), MixError::InvalidFormat(_)));
+    /// assert!(matches!(MixError::Cancelled.context("reading `ra2.mix`"), MixError::Cancelled));
+    /// ```
+    pub fn context(self, context: impl Into<String>) -> Self {
         match self {
-            MixError::IoError(e) => {
-                write!(f, "IO error: {}", e)
-            }
-            MixError::CryptoError(e) => {
-                write!(f, "Crypto error:: {}", e)
-            }
-            MixError::InvalidFormat(e) => {
-                write!(f, "Invalid file format: {}", e)
-            }
-            MixError::FileNotFound(e) => {
-                write!(f, "File not found: {}", e)
-            }
+            // Stopping on request is not a failure of what was being done
+            MixError::Cancelled => self,
+            _ => Self::Context { context: context.into(), source: Box::new(self) },
+        }
+    }
+
+    /// The innermost error, skipping every [`MixError::Context`] layer
+    pub fn root(&self) -> &MixError {
+        match self {
+            MixError::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Checks that `needed` bytes are available at `offset` in data of `length` bytes
+    pub(crate) fn check_bounds(offset: usize, needed: usize, length: usize) -> Result<()> {
+        if offset.checked_add(needed).is_none_or(|end| end > length) {
+            return Err(MixError::Truncated {
+                offset: offset as u64,
+                needed: needed as u64,
+                available: length.saturating_sub(offset) as u64,
+            });
         }
+        Ok(())
     }
 }
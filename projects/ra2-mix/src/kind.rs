@@ -0,0 +1,160 @@
+//! Detection of the file type of archive entries
+
+use crate::{
+    MixIndex,
+    constants::{CHECKSUM_SIZE, XCC_ID_BYTES},
+};
+use std::fmt::{Display, Formatter};
+
+/// The file type of an entry, detected from its name or contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum EntryKind {
+    /// A nested MIX archive
+    Mix,
+    /// A local mix database
+    Database,
+    /// An INI file, such as `rulesmd.ini`
+    Ini,
+    /// A string table
+    Csf,
+    /// A map, which is INI text
+    Map,
+    /// Any other text file
+    Text,
+    /// A sprite
+    Shp,
+    /// A voxel model
+    Vxl,
+    /// A voxel animation
+    Hva,
+    /// A palette
+    Pal,
+    /// A terrain tile
+    Tmp,
+    /// A Westwood compressed sound
+    Aud,
+    /// A wave sound
+    Wav,
+    /// A PCX image
+    Pcx,
+    /// A Bink video
+    Bik,
+    /// Anything else
+    Unknown,
+}
+
+impl EntryKind {
+    /// Detects the type of an entry, from the extension of its name if known and from its contents otherwise
+    ///
+    /// # Arguments
+    /// * `name` - The file name of the entry, if known
+    /// * `data` - The entry data
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{EntryKind, MixPackage};
+    ///
+    /// assert_eq!(EntryKind::detect(Some("RULESMD.INI"), b""), EntryKind::Ini);
+    /// assert_eq!(EntryKind::detect(None, b"[General]\r\nName=Test"), EntryKind::Ini);
+    ///
+    /// let mut mix = MixPackage::default();
+    /// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+    /// assert_eq!(EntryKind::detect(None, &mix.encode().unwrap()), EntryKind::Mix);
+    /// ```
+    pub fn detect(name: Option<&str>, data: &[u8]) -> Self {
+        let extension = name.and_then(|name| name.rsplit_once('.')).map(|(_, extension)| extension.to_ascii_lowercase());
+        let by_name = match extension.as_deref() {
+            Some("mix" | "mmx" | "yro") => EntryKind::Mix,
+            Some("dat") if data.starts_with(XCC_ID_BYTES) => EntryKind::Database,
+            Some("ini") => EntryKind::Ini,
+            Some("csf") => EntryKind::Csf,
+            Some("map" | "mpr" | "yrm") => EntryKind::Map,
+            Some("txt" | "csv") => EntryKind::Text,
+            Some("shp") => EntryKind::Shp,
+            Some("vxl") => EntryKind::Vxl,
+            Some("hva") => EntryKind::Hva,
+            Some("pal") => EntryKind::Pal,
+            Some("tem" | "sno" | "urb" | "ubn" | "des" | "lun" | "tmp") => EntryKind::Tmp,
+            Some("aud") => EntryKind::Aud,
+            Some("wav") => EntryKind::Wav,
+            Some("pcx") => EntryKind::Pcx,
+            Some("bik") => EntryKind::Bik,
+            _ => EntryKind::Unknown,
+        };
+        if by_name != EntryKind::Unknown {
+            return by_name;
+        }
+        EntryKind::detect_contents(data)
+    }
+    /// Detects the type of an entry from its contents alone
+    fn detect_contents(data: &[u8]) -> Self {
+        if data.starts_with(XCC_ID_BYTES) {
+            return EntryKind::Database;
+        }
+        if data.starts_with(b" FSC") {
+            return EntryKind::Csf;
+        }
+        if data.starts_with(b"RIFF") {
+            return EntryKind::Wav;
+        }
+        if data.starts_with(b"BIK") {
+            return EntryKind::Bik;
+        }
+        if is_mix(data) {
+            return EntryKind::Mix;
+        }
+        if !data.is_empty() && is_text(data) {
+            let first = data.iter().find(|byte| !byte.is_ascii_whitespace() && **byte != b';');
+            return if first == Some(&b'[') { EntryKind::Ini } else { EntryKind::Text };
+        }
+        EntryKind::Unknown
+    }
+    /// Checks if the entry is text that can be shown as is
+    pub fn is_text(&self) -> bool {
+        matches!(self, EntryKind::Ini | EntryKind::Map | EntryKind::Text)
+    }
+}
+
+impl Display for EntryKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            EntryKind::Mix => "MIX",
+            EntryKind::Database => "LMD",
+            EntryKind::Ini => "INI",
+            EntryKind::Csf => "CSF",
+            EntryKind::Map => "MAP",
+            EntryKind::Text => "TXT",
+            EntryKind::Shp => "SHP",
+            EntryKind::Vxl => "VXL",
+            EntryKind::Hva => "HVA",
+            EntryKind::Pal => "PAL",
+            EntryKind::Tmp => "TMP",
+            EntryKind::Aud => "AUD",
+            EntryKind::Wav => "WAV",
+            EntryKind::Pcx => "PCX",
+            EntryKind::Bik => "BIK",
+            EntryKind::Unknown => "?",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Checks if the data is a MIX archive whose index fits the data exactly
+fn is_mix(data: &[u8]) -> bool {
+    match MixIndex::parse(data) {
+        Ok(index) if !index.entries.is_empty() => {
+            let body_end = index.body_offset + u64::from(index.header.data_size);
+            let length = data.len() as u64;
+            let fits = body_end == length || (index.header.has_checksum() && body_end + CHECKSUM_SIZE as u64 == length);
+            fits && index.entries.iter().all(|entry| entry.range(u64::from(index.header.data_size)).is_ok())
+        }
+        _ => false,
+    }
+}
+
+/// Checks if the start of the data is free of control characters, allowing any 8-bit code page
+fn is_text(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(4096)];
+    sample.iter().all(|byte| !byte.is_ascii_control() || matches!(byte, b'\r' | b'\n' | b'\t' | 0x1A))
+}
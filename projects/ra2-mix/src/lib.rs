@@ -9,14 +9,52 @@
 //! This library provides functionality for reading and writing Red Alert 2 MIX files.
 //! It supports both encrypted and unencrypted MIX files, and can extract files from MIX archives.
 
+mod catalog;
 mod checksum;
 mod constants;
 mod crypto;
+mod diagnostics;
 mod errors;
+mod kind;
+mod listing;
+#[cfg(feature = "manifest")]
+mod manifest;
+mod parallel;
+mod progress;
+mod search;
+mod shadow;
+mod source;
+#[cfg(feature = "watch")]
+mod watch;
 mod xcc_package;
 
 pub use crate::{
+    catalog::{Catalog, CatalogArchive, CatalogEntry, CatalogHit, CatalogUpdate},
+    checksum::{content_hash, ra2_crc},
     constants::XccGame,
-    errors::{MixError, Result},
-    xcc_package::{MixPackage, extract, patch},
+    diagnostics::MixWarning,
+    errors::{CryptoStage, MixError, Result},
+    kind::EntryKind,
+    listing::{ListingEntry, MixListing},
+    progress::{CancellationToken, MixTask, Progress, ProgressObserver, ProgressStage},
+    search::{SearchHit, SearchQuery, search},
+    shadow::{ShadowCopy, ShadowGroup, ShadowReport},
+    source::{ArchiveSource, DirectorySource, EntryMetadata},
+    xcc_package::{
+        MixEntry, MixHeader, MixPackage,
+        database::{DatabasePolicy, LocalMixDatabase},
+        diff::{ChangeKind, EntryChange, LineChange, MixDiff},
+        extract, extract_path, extract_with,
+        index::MixIndex,
+        merge::{ConflictPolicy, MergeConflict, MergeReport},
+        patch, patch_with,
+        stream::{EntryReader, MixReader},
+        writer::{BodyLayout, EntryOrder, VolumeLimits},
+    },
 };
+#[cfg(feature = "manifest")]
+pub use crate::manifest::{ArchiveManifest, Manifest};
+#[cfg(feature = "tokio")]
+pub use crate::xcc_package::async_io::AsyncMixReader;
+#[cfg(feature = "watch")]
+pub use crate::watch::{MixWatcher, WatchSummary};
@@ -0,0 +1,200 @@
+//! Listings of the entries of an archive with their detected types and content hashes, exported as JSON or CSV
+
+use crate::{
+    EntryKind, MixError, MixHeader, MixReader, MixWarning, XccGame,
+    checksum::content_hash,
+    diagnostics::report,
+    xcc_package::reader::out_of_bounds,
+};
+use std::{
+    borrow::Cow,
+    io::{Read, Seek},
+    path::Path,
+};
+
+/// An entry of a [`MixListing`]
+///
+/// The file ID and the hash are exported as 8 hexadecimal digits, in JSON and CSV alike.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListingEntry {
+    /// The file ID
+    #[cfg_attr(feature = "serde_json", serde(with = "hex"))]
+    pub id: i32,
+    /// The file name, if the archive knows it
+    pub name: Option<String>,
+    /// The file type, detected from the name or the data
+    pub kind: EntryKind,
+    /// The offset of the data in the body
+    pub offset: u64,
+    /// The size of the data in bytes
+    pub size: u64,
+    /// The [`content_hash`] of the data
+    #[cfg_attr(feature = "serde_json", serde(with = "hex"))]
+    pub hash: u32,
+    /// Whether the entry points outside the body, it is then listed as empty
+    pub out_of_bounds: bool,
+}
+
+/// The header, game and entries of an archive, to compare releases or feed reports
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{MixListing, MixPackage, MixReader};
+/// use std::io::Cursor;
+///
+/// let mut mix = MixPackage::default();
+/// mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+/// let mut reader = MixReader::new(Cursor::new(mix.encode().unwrap())).unwrap();
+/// let listing = MixListing::read(&mut reader).unwrap();
+/// let csv = listing.to_csv();
+/// assert!(csv.starts_with("id,name,kind,offset,size,hash\n"));
+/// assert!(csv.contains(",rulesmd.ini,INI,"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct MixListing {
+    /// The game of the archive, read from the local mix database or guessed from the entries
+    pub game: XccGame,
+    /// The decoded header
+    pub header: MixHeader,
+    /// The offset of the body from the start of the file
+    pub body_offset: u64,
+    /// The entries in index order
+    pub entries: Vec<ListingEntry>,
+}
+
+impl MixListing {
+    /// Lists an archive from disk
+    ///
+    /// # Arguments
+    /// * `path` - Path to the MIX file
+    pub fn open(path: &Path) -> Result<Self, MixError> {
+        let mut reader = MixReader::open(path)?;
+        Self::read(&mut reader).map_err(|e| e.context(format!("Failed to list `{}`", path.display())))
+    }
+    /// Lists an archive, reading every entry to detect its type and hash its data
+    ///
+    /// # Arguments
+    /// * `reader` - The opened archive
+    pub fn read<R: Read + Seek>(reader: &mut MixReader<R>) -> Result<Self, MixError> {
+        Ok(Self::read_with_warnings(reader)?.0)
+    }
+    /// Lists an archive, returning the warnings found along the way
+    ///
+    /// Every index entry is read from its own range. Entries outside the body, as protected archives have, are listed
+    /// as empty and reported as [`MixWarning::EntryOutOfBounds`].
+    ///
+    /// # Arguments
+    /// * `reader` - The opened archive
+    ///
+    /// # Returns
+    /// The listing and every [`MixWarning`] of the reader and the entries
+    pub fn read_with_warnings<R: Read + Seek>(reader: &mut MixReader<R>) -> Result<(Self, Vec<MixWarning>), MixError> {
+        let index = reader.index().clone();
+        let mut warnings = reader.warnings().to_vec();
+        let mut entries = Vec::with_capacity(index.entries.len());
+        for entry in &index.entries {
+            let mut data = Vec::new();
+            let out_of_bounds = match reader.entry_at(entry) {
+                Ok(mut view) => {
+                    view.read_to_end(&mut data)?;
+                    false
+                }
+                Err(MixError::InvalidIndex { .. }) => {
+                    // The reader already reported a local mix database outside the body
+                    let warning = out_of_bounds(entry);
+                    if !warnings.contains(&warning) {
+                        report(&mut warnings, warning);
+                    }
+                    true
+                }
+                Err(e) => return Err(e),
+            };
+            let name = reader.name(entry.id).map(|name| name.to_string());
+            entries.push(ListingEntry {
+                id: entry.id,
+                kind: EntryKind::detect(name.as_deref(), &data),
+                name,
+                offset: entry.offset as u32 as u64,
+                size: data.len() as u64,
+                hash: content_hash(&data),
+                out_of_bounds,
+            });
+        }
+        let listing = Self { game: reader.game(), header: index.header, body_offset: index.body_offset, entries };
+        Ok((listing, warnings))
+    }
+    /// The total size of the entries in bytes
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+    /// Exports the listing as pretty-printed JSON, with file IDs and hashes in hexadecimal as printed by the CLI
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> Result<String, MixError> {
+        serde_json::to_string_pretty(self).map_err(|e| MixError::IoError(e.into()))
+    }
+    /// Exports the entries as CSV with a header row, with file IDs and hashes in hexadecimal as printed by the CLI
+    ///
+    /// The columns are `id`, `name`, `kind`, `offset`, `size` and `hash`, unknown names are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("id,name,kind,offset,size,hash\n");
+        for entry in &self.entries {
+            let name = entry.name.as_deref().map(csv_field).unwrap_or_default();
+            csv.push_str(&format!(
+                "{:08X},{},{},{},{},{:08X}\n",
+                entry.id, name, entry.kind, entry.offset, entry.size, entry.hash
+            ));
+        }
+        csv
+    }
+}
+
+/// Serializes file IDs and hashes as 8 hexadecimal digits, negative IDs as their unsigned bits
+#[cfg(feature = "serde_json")]
+mod hex {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    /// A 32-bit number written in hexadecimal
+    pub trait Hex: Sized {
+        fn from_bits(bits: u32) -> Self;
+        fn to_bits(&self) -> u32;
+    }
+
+    impl Hex for i32 {
+        fn from_bits(bits: u32) -> Self {
+            bits as i32
+        }
+        fn to_bits(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    impl Hex for u32 {
+        fn from_bits(bits: u32) -> Self {
+            bits
+        }
+        fn to_bits(&self) -> u32 {
+            *self
+        }
+    }
+
+    pub fn serialize<T: Hex, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:08X}", value.to_bits()))
+    }
+
+    pub fn deserialize<'de, T: Hex, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        u32::from_str_radix(&text, 16).map(T::from_bits).map_err(|e| D::Error::custom(format!("invalid hex `{}`: {}", text, e)))
+    }
+}
+
+/// Quotes a CSV field if it holds a separator, a quote or a line break
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    }
+    else {
+        Cow::Borrowed(text)
+    }
+}
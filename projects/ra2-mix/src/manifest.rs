@@ -0,0 +1,211 @@
+//! Declarative archive builds described in a TOML or JSON manifest
+//!
+//! A manifest lists the archives to build, the files that go in each of them and how they are written:
+//!
+//! ```toml
+//! game = "RA2_YR"
+//!
+//! [[archive]]
+//! name = "expandmd01.mix"
+//! sources = ["rules/*.ini", "art/*.shp"]
+//! mixes = ["maps.mix"]
+//! database = "merge"
+//! checksum = true
+//!
+//! [[archive]]
+//! name = "maps.mix"
+//! sources = ["maps/*.map"]
+//! ```
+//!
+//! Archives listed in `mixes` are built first and packed into the archive that lists them instead of being written
+//! on their own.
+
+use crate::{DatabasePolicy, MixError, MixPackage, XccGame};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// A set of archives to build
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The game of every archive that does not set its own
+    #[serde(default)]
+    pub game: XccGame,
+    /// The archives to build
+    #[serde(default, rename = "archive")]
+    pub archives: Vec<ArchiveManifest>,
+}
+
+/// One archive of a [`Manifest`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveManifest {
+    /// The file name of the archive
+    pub name: String,
+    /// Glob patterns of the files to pack, relative to the manifest folder
+    ///
+    /// Files are stored under their file name, files matched by later patterns replace earlier ones with the same file ID,
+    /// whatever the case of their names.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// The names of other archives of the manifest to build first and pack into this one
+    #[serde(default)]
+    pub mixes: Vec<String>,
+    /// The game of the archive, the manifest game if not set
+    #[serde(default)]
+    pub game: Option<XccGame>,
+    /// How the local mix database is written
+    #[serde(default)]
+    pub database: DatabasePolicy,
+    /// Whether the header is encrypted, which is not supported yet
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Whether a SHA-1 digest of the body is appended
+    #[serde(default)]
+    pub checksum: bool,
+}
+
+impl Manifest {
+    /// Reads a manifest from disk, as JSON if the file name ends with `.json` and as TOML otherwise
+    ///
+    /// # Arguments
+    /// * `path` - Path to the manifest
+    pub fn load(path: &Path) -> Result<Self, MixError> {
+        let context = |e: MixError| e.context(format!("Failed to load `{}`", path.display()));
+        let text = std::fs::read_to_string(path).map_err(|e| context(e.into()))?;
+        let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        if is_json { Manifest::from_json(&text) } else { Manifest::from_toml(&text) }.map_err(context)
+    }
+    /// Parses a TOML manifest
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{DatabasePolicy, Manifest, XccGame};
+    ///
+    /// let manifest = Manifest::from_toml(
+    ///     r#"
+    ///     game = "RA2_YR"
+    ///     [[archive]]
+    ///     name = "expandmd01.mix"
+    ///     sources = ["*.ini"]
+    ///     database = "omit"
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(manifest.game, XccGame::RA2_YR);
+    /// assert_eq!(manifest.archives[0].database, DatabasePolicy::Omit);
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Self, MixError> {
+        toml::from_str(text).map_err(|e| MixError::InvalidFormat(format!("Invalid manifest: {}", e)))
+    }
+    /// Parses a JSON manifest, with the same fields as the TOML format
+    pub fn from_json(text: &str) -> Result<Self, MixError> {
+        serde_json::from_str(text).map_err(|e| MixError::InvalidFormat(format!("Invalid manifest: {}", e)))
+    }
+    /// Builds every archive that is not packed into another one
+    ///
+    /// # Arguments
+    /// * `base` - The folder source patterns are relative to, usually the manifest folder
+    ///
+    /// # Returns
+    /// The encoded archives by file name
+    pub fn build(&self, base: &Path) -> Result<BTreeMap<String, Vec<u8>>, MixError> {
+        let nested: HashSet<&str> = self.archives.iter().flat_map(|archive| archive.mixes.iter().map(String::as_str)).collect();
+        let mut built = BTreeMap::new();
+        for archive in &self.archives {
+            self.build_archive(archive, base, &mut built, &mut Vec::new())?;
+        }
+        built.retain(|name, _| !nested.contains(name.as_str()));
+        Ok(built)
+    }
+    /// Builds every archive that is not packed into another one and writes them to a folder
+    ///
+    /// # Arguments
+    /// * `base` - The folder source patterns are relative to, usually the manifest folder
+    /// * `output` - The folder to write the archives to
+    ///
+    /// # Returns
+    /// The paths of the written archives
+    pub fn build_to(&self, base: &Path, output: &Path) -> Result<Vec<PathBuf>, MixError> {
+        std::fs::create_dir_all(output)?;
+        let mut written = Vec::new();
+        for (name, data) in self.build(base)? {
+            let path = output.join(name);
+            std::fs::write(&path, data)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+    /// Builds an archive after the archives it packs, keeping every built archive in `built`
+    fn build_archive(
+        &self,
+        archive: &ArchiveManifest,
+        base: &Path,
+        built: &mut BTreeMap<String, Vec<u8>>,
+        building: &mut Vec<String>,
+    ) -> Result<(), MixError> {
+        if built.contains_key(&archive.name) {
+            return Ok(());
+        }
+        if building.contains(&archive.name) {
+            return Err(MixError::InvalidFormat(format!("Invalid manifest: `{}` contains itself", archive.name)));
+        }
+        let context = |e: MixError| e.context(format!("Failed to build `{}`", archive.name));
+        if archive.encrypted {
+            return Err(context(MixError::Unsupported("writing encrypted archives".to_string())));
+        }
+
+        building.push(archive.name.clone());
+        let mut package = MixPackage {
+            game: archive.game.unwrap_or(self.game),
+            database_policy: archive.database,
+            checksum: archive.checksum,
+            ..Default::default()
+        };
+        for pattern in &archive.sources {
+            for path in glob_files(base, pattern).map_err(context)? {
+                let name = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => name.to_string(),
+                    None => return Err(context(MixError::FileNotFound(path))),
+                };
+                let data = std::fs::read(&path).map_err(|e| context(e.into()))?;
+                package.insert(name, data);
+            }
+        }
+        for name in &archive.mixes {
+            let nested = match self.archives.iter().find(|other| &other.name == name) {
+                Some(nested) => nested,
+                None => {
+                    let error = MixError::InvalidFormat(format!("Invalid manifest: no archive named `{}`", name));
+                    return Err(context(error));
+                }
+            };
+            self.build_archive(nested, base, built, building)?;
+            package.insert(name.clone(), built[name].clone());
+        }
+        building.pop();
+
+        built.insert(archive.name.clone(), package.encode().map_err(context)?);
+        Ok(())
+    }
+}
+
+/// Lists the files matching a pattern relative to `base`, in path order
+fn glob_files(base: &Path, pattern: &str) -> Result<Vec<PathBuf>, MixError> {
+    // The base folder is matched literally, even if its name holds wildcards or brackets
+    let full = Path::new(&glob::Pattern::escape(&base.to_string_lossy())).join(pattern);
+    let paths = glob::glob(&full.to_string_lossy())
+        .map_err(|e| MixError::InvalidFormat(format!("Invalid pattern `{}`: {}", pattern, e)))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| MixError::IoError(e.into()))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,55 @@
+//! Optional multithreading for work done entry by entry
+//!
+//! With the `parallel` feature the helpers spread the work across the rayon thread pool, otherwise they run it on the
+//! calling thread. Results always come back in input order, so the output does not depend on the feature.
+//!
+//! Encoding hashes the names and copies the bodies into the output in parallel, extraction writes files and diffs
+//! compare entries in parallel. The SHA-1 digest of a checksummed archive is a single stream
+//! and is always computed on the calling thread.
+
+/// Applies `f` to every item, returning the results in input order
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter().map(f).collect()
+    }
+}
+
+/// Applies `f` to every item in place
+pub(crate) fn par_for_each<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        items.par_iter_mut().for_each(f)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items.iter_mut().for_each(f)
+    }
+}
+
+/// The number of entries processed between two progress reports
+pub(crate) fn batch_size() -> usize {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::current_num_threads() * 4
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        1
+    }
+}
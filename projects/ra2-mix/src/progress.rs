@@ -0,0 +1,136 @@
+//! Progress reporting and cancellation for long-running MIX operations
+
+use crate::MixError;
+use std::{
+    fmt::{Debug, Formatter},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// The step of an operation that reported progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStage {
+    /// Reading entries out of an archive
+    Decode,
+    /// Writing entries into an archive
+    Encode,
+    /// Writing entries to a folder
+    Extract,
+    /// Reading files from a folder
+    Collect,
+}
+
+/// Progress of an operation after an entry was processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The step of the operation
+    pub stage: ProgressStage,
+    /// The number of entries processed so far
+    pub entry: usize,
+    /// The number of entries of the step
+    pub entries: usize,
+    /// The number of bytes processed so far
+    pub bytes: u64,
+    /// The number of bytes of the step
+    pub total_bytes: u64,
+}
+
+/// Receives progress updates from long-running operations
+pub trait ProgressObserver {
+    /// Called after every processed entry
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressObserver for F {
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
+
+/// Shared flag that asks running operations to stop
+///
+/// Clones share the same flag, so a token can be handed to a worker thread and cancelled from the UI thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Asks every operation using this token to stop with [`MixError::Cancelled`]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+    /// Checks whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress observer and cancellation token of a running operation
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{CancellationToken, MixPackage, MixTask, Progress};
+///
+/// let mut mix = MixPackage::default();
+/// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+///
+/// let mut last = None;
+/// let mut observer = |progress: &Progress| last = Some(progress.entry);
+/// let mut task = MixTask::new().with_observer(&mut observer);
+/// mix.encode_with(&mut task).unwrap();
+/// assert_eq!(last, Some(2));
+///
+/// let token = CancellationToken::new();
+/// token.cancel();
+/// let mut task = MixTask::new().with_cancellation(token);
+/// assert!(MixPackage::default().encode_with(&mut task).is_err());
+/// ```
+#[derive(Default)]
+pub struct MixTask<'a> {
+    observer: Option<&'a mut dyn ProgressObserver>,
+    cancellation: CancellationToken,
+}
+
+impl Debug for MixTask<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MixTask")
+            .field("observer", &self.observer.is_some())
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl<'a> MixTask<'a> {
+    /// Creates a task without observer that is never cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reports progress to the observer
+    pub fn with_observer(mut self, observer: &'a mut dyn ProgressObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+    /// Stops the operation when the token is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+    /// Fails with [`MixError::Cancelled`] if the token was cancelled
+    pub(crate) fn check(&self) -> Result<(), MixError> {
+        if self.cancellation.is_cancelled() { Err(MixError::Cancelled) } else { Ok(()) }
+    }
+    /// Reports the progress of an entry, then checks for cancellation
+    pub(crate) fn step(&mut self, progress: Progress) -> Result<(), MixError> {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_progress(&progress);
+        }
+        self.check()
+    }
+}
@@ -0,0 +1,174 @@
+//! Search for entries across archives by name, file ID, content hash or content
+
+use crate::{
+    CatalogEntry, MixError,
+    catalog::{find_archives, walk_entries},
+    checksum::content_hash,
+};
+use std::path::PathBuf;
+
+/// What to look for in [`search`] and [`Catalog::search`](crate::Catalog::search)
+#[derive(Debug, Clone)]
+pub enum SearchQuery {
+    /// Names matching a pattern with `*` and `?` wildcards, ignoring case
+    Glob(String),
+    /// Names matching a regular expression
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+    /// Entries with a file ID, see [`ra2_crc`](crate::ra2_crc) to search for a name
+    Id(i32),
+    /// Entries with a [`content_hash`]
+    Hash(u32),
+    /// Entries whose data contains a byte sequence, such as a text
+    Bytes(Vec<u8>),
+}
+
+impl SearchQuery {
+    /// Checks an entry against the query
+    ///
+    /// # Arguments
+    /// * `id` - The file ID of the entry
+    /// * `name` - The file name of the entry, if known, entries without names never match name patterns
+    /// * `data` - The entry data
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::SearchQuery;
+    ///
+    /// let query = SearchQuery::Glob("ga*.SHP".to_string());
+    /// assert!(query.is_match(0, Some("gapowr.shp"), b""));
+    /// assert!(!query.is_match(0, Some("gapowr.vxl"), b""));
+    /// assert!(SearchQuery::Bytes(b"Name=".to_vec()).is_match(0, None, b"[General]\nName=Test"));
+    /// ```
+    pub fn is_match(&self, id: i32, name: Option<&str>, data: &[u8]) -> bool {
+        match self {
+            SearchQuery::Bytes(pattern) => contains(data, pattern),
+            SearchQuery::Hash(hash) => content_hash(data) == *hash,
+            _ => self.matches_listing(id, name, 0).unwrap_or(false),
+        }
+    }
+    /// Checks an entry from its listing alone, `None` if the query needs the data
+    pub(crate) fn matches_listing(&self, id: i32, name: Option<&str>, hash: u32) -> Option<bool> {
+        Some(match self {
+            SearchQuery::Glob(pattern) => name.is_some_and(|name| glob_match(pattern, name)),
+            #[cfg(feature = "regex")]
+            SearchQuery::Regex(regex) => name.is_some_and(|name| regex.is_match(name)),
+            SearchQuery::Id(query) => id == *query,
+            SearchQuery::Hash(query) => hash == *query,
+            SearchQuery::Bytes(_) => return None,
+        })
+    }
+}
+
+/// An entry found by [`search`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchHit {
+    /// The archive file that holds the entry
+    pub archive: PathBuf,
+    /// The entry, with the nested archives that contain it
+    pub entry: CatalogEntry,
+}
+
+impl SearchHit {
+    /// The archive file followed by the nested archives that contain the entry
+    pub fn chain(&self) -> Vec<String> {
+        let file = self.archive.to_string_lossy().replace('\\', "/");
+        std::iter::once(file).chain(self.entry.nested.iter().cloned()).collect()
+    }
+}
+
+/// Searches archives and the archives nested in them
+///
+/// Entries of nested archives are searched too, the nested archive itself matches content queries for the content of
+/// its entries.
+///
+/// # Arguments
+/// * `inputs` - Archive files, and folders whose archives are searched recursively, skipping invalid ones
+/// * `query` - What to look for
+///
+/// # Examples
+/// ```no_run
+/// use ra2_mix::{SearchQuery, search};
+/// use std::path::PathBuf;
+///
+/// let query = SearchQuery::Glob("gapowr.*".to_string());
+/// for hit in search(&[PathBuf::from("C:/Westwood/RA2")], &query).unwrap() {
+///     println!("{}", hit.chain().join(" > "));
+/// }
+/// ```
+pub fn search(inputs: &[PathBuf], query: &SearchQuery) -> Result<Vec<SearchHit>, MixError> {
+    let mut hits = Vec::new();
+    for input in inputs {
+        let explicit = !input.is_dir();
+        let mut files = Vec::new();
+        if explicit {
+            files.push(input.clone());
+        }
+        else {
+            // Like archives that fail to parse, unreadable subfolders are skipped
+            find_archives(input, &mut files)?;
+            files.sort();
+        }
+        for file in files {
+            let context = |e: MixError| e.context(format!("Failed to search `{}`", file.display()));
+            let data = std::fs::read(&file).map_err(|e| context(e.into()))?;
+            let mut entries = Vec::new();
+            // Hashing every entry is slow, only the hits get their hash
+            match walk_entries(&data, 0, &mut Vec::new(), false, &mut entries) {
+                Ok(()) => {}
+                Err(e) if explicit => return Err(context(e)),
+                Err(_) => continue,
+            }
+            for (mut entry, data) in entries {
+                if query.is_match(entry.id, entry.name.as_deref(), data) {
+                    entry.hash = content_hash(data);
+                    hits.push(SearchHit { archive: file.clone(), entry });
+                }
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Checks if the data contains the pattern
+pub(crate) fn contains(data: &[u8], pattern: &[u8]) -> bool {
+    match pattern.first() {
+        None => true,
+        Some(first) => data
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| *byte == first)
+            .any(|(start, _)| data[start..].starts_with(pattern)),
+    }
+}
+
+/// Matches a name against a pattern with `*` and `?` wildcards, ignoring ASCII case
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut p, mut n) = (0, 0);
+    // The position after the last `*` and the name position it matched up to, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    p = after;
+                    n = matched + 1;
+                    star = Some((after, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,174 @@
+//! Report of files that exist in several archives and which copy the game loads
+
+use crate::{Catalog, CatalogHit, checksum::ra2_crc, constants::MIX_DB_FILENAME};
+use std::{cmp::Reverse, collections::BTreeMap};
+
+/// Archive names in the order Red Alert 2 and Yuri's Revenge look files up, earlier archives win
+///
+/// `##` stands for two digits, higher numbers are looked up first.
+const LOAD_ORDER: &[&str] = &[
+    "expandmd##.mix",
+    "expand##.mix",
+    "ra2md.mix",
+    "ra2.mix",
+    "langmd.mix",
+    "language.mix",
+    "cachemd.mix",
+    "cache.mix",
+    "localmd.mix",
+    "local.mix",
+    "ntrlmd.mix",
+    "neutral.mix",
+    "conqmd.mix",
+    "conquer.mix",
+    "genermd.mix",
+    "generic.mix",
+    "isogenmd.mix",
+    "isogen.mix",
+    "cameomd.mix",
+    "cameo.mix",
+    "multimd.mix",
+    "multi.mix",
+    "mapsmd##.mix",
+    "maps##.mix",
+    "thememd.mix",
+    "theme.mix",
+    "audiomd.mix",
+    "audio.mix",
+    "movmd##.mix",
+    "movies##.mix",
+];
+
+/// Where an archive name sits in [`LOAD_ORDER`], `None` for archives the game does not load by name
+fn load_rank(name: &str) -> Option<(usize, Reverse<u32>)> {
+    let name = name.rsplit('/').next().unwrap_or(name).to_ascii_lowercase();
+    LOAD_ORDER.iter().enumerate().find_map(|(rank, pattern)| match pattern.split_once("##") {
+        None => (name == *pattern).then_some((rank, Reverse(0))),
+        Some((prefix, suffix)) => {
+            let digits = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            let number = if digits.len() == 2 { digits.parse().ok()? } else { return None };
+            Some((rank, Reverse(number)))
+        }
+    })
+}
+
+/// The position of an entry in the load order, smaller wins, `None` if the game never loads it
+///
+/// The innermost archive the load order names decides, so an unknown archive nested in `expandmd01.mix` loads with
+/// it. Ties are broken by path.
+fn load_key(chain: &[String]) -> Option<((usize, Reverse<u32>), String)> {
+    let rank = chain.iter().rev().find_map(|name| load_rank(name))?;
+    Some((rank, chain.join("/").to_ascii_lowercase()))
+}
+
+/// A copy of a file in a [`ShadowGroup`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShadowCopy {
+    /// The archive file followed by the nested archives that contain the copy
+    pub chain: Vec<String>,
+    /// The size of the copy in bytes
+    pub size: u64,
+    /// The [`content_hash`](crate::content_hash) of the copy
+    pub hash: u32,
+    /// Whether the copy has the same size and hash as the copy the game loads
+    pub identical: bool,
+}
+
+/// A file ID that exists in more than one archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShadowGroup {
+    /// The file ID
+    pub id: i32,
+    /// The file name, if any archive knows it
+    pub name: Option<String>,
+    /// The copy the game loads
+    pub winner: ShadowCopy,
+    /// The copies hidden by the winner, in load order
+    pub shadowed: Vec<ShadowCopy>,
+    /// The copies in archives the game does not load, by path
+    pub not_loaded: Vec<ShadowCopy>,
+}
+
+/// Every file of a game installation that exists in more than one archive
+///
+/// Only files the game loads from one of the archives are reported. Copies in archives outside the load order, such as
+/// a mod archive that was never renamed to `expandmd##.mix`, are listed apart and left out of the counts.
+///
+/// # Examples
+/// ```no_run
+/// use ra2_mix::{Catalog, ShadowReport};
+/// use std::path::Path;
+///
+/// let catalog = Catalog::build(Path::new("C:/Westwood/RA2")).unwrap();
+/// let report = ShadowReport::new(&catalog);
+/// for group in &report.groups {
+///     println!("{:?} is loaded from {}", group.name, group.winner.chain.join(" > "));
+/// }
+/// println!("{} bytes in identical copies", report.wasted);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShadowReport {
+    /// The duplicated files, sorted by file ID
+    pub groups: Vec<ShadowGroup>,
+    /// The number of shadowed copies identical to the copy the game loads
+    pub identical: usize,
+    /// The number of shadowed copies that differ from the copy the game loads
+    pub different: usize,
+    /// The bytes taken by shadowed copies identical to the copy the game loads
+    pub wasted: u64,
+}
+
+impl ShadowReport {
+    /// Finds the files of a catalog that exist in more than one archive, ignoring the local mix databases
+    pub fn new(catalog: &Catalog) -> Self {
+        let database_id = ra2_crc(MIX_DB_FILENAME);
+        let mut by_id: BTreeMap<i32, Vec<CatalogHit<'_>>> = BTreeMap::new();
+        for hit in catalog.entries().filter(|hit| hit.entry.id != database_id) {
+            by_id.entry(hit.entry.id).or_default().push(hit);
+        }
+
+        let mut report = ShadowReport::default();
+        for (id, hits) in by_id.into_iter().filter(|(_, hits)| hits.len() > 1) {
+            let mut loaded = Vec::new();
+            let mut not_loaded = Vec::new();
+            for hit in hits {
+                let chain = hit.chain();
+                match load_key(&chain) {
+                    Some(key) => loaded.push((key, hit)),
+                    None => not_loaded.push((chain, hit)),
+                }
+            }
+            if loaded.is_empty() {
+                continue;
+            }
+            loaded.sort_by(|(a, _), (b, _)| a.cmp(b));
+            not_loaded.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let first = loaded[0].1.entry;
+            let copy = |hit: &CatalogHit<'_>| ShadowCopy {
+                chain: hit.chain(),
+                size: hit.entry.size,
+                hash: hit.entry.hash,
+                identical: hit.entry.size == first.size && hit.entry.hash == first.hash,
+            };
+            let shadowed: Vec<ShadowCopy> = loaded[1..].iter().map(|(_, hit)| copy(hit)).collect();
+            for shadow in &shadowed {
+                if shadow.identical {
+                    report.identical += 1;
+                    report.wasted += shadow.size;
+                }
+                else {
+                    report.different += 1;
+                }
+            }
+            let mut hits = loaded.iter().map(|(_, hit)| hit).chain(not_loaded.iter().map(|(_, hit)| hit));
+            let name = hits.find_map(|hit| hit.entry.name.clone());
+            let winner = copy(&loaded[0].1);
+            let not_loaded = not_loaded.iter().map(|(_, hit)| copy(hit)).collect();
+            report.groups.push(ShadowGroup { id, name, winner, shadowed, not_loaded });
+        }
+        report
+    }
+}
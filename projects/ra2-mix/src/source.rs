@@ -0,0 +1,178 @@
+//! Uniform access to MIX archives, folders and in-memory packages
+
+use crate::{MixEntry, MixError, MixPackage, MixReader, ra2_crc};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+/// A container of named entries, such as a MIX archive or a game folder
+///
+/// Names are matched case-insensitively, like the engine does.
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{ArchiveSource, MixPackage};
+/// use std::io::Read;
+///
+/// let mut mix = MixPackage::default();
+/// mix.add_any("rules.ini".to_string(), b"[General]".to_vec());
+///
+/// let source: &mut dyn ArchiveSource = &mut mix;
+/// assert!(source.contains("RULES.INI"));
+/// assert_eq!(source.metadata("rules.ini").unwrap().size, 9);
+/// let mut text = String::new();
+/// source.open("Rules.ini").unwrap().read_to_string(&mut text).unwrap();
+/// assert_eq!(text, "[General]");
+/// ```
+pub trait ArchiveSource {
+    /// Lists every entry of the source
+    fn list(&self) -> Vec<EntryMetadata>;
+    /// Opens an entry for reading
+    ///
+    /// # Returns
+    /// The entry data, or [`MixError::FileNotFound`] if the source has no such entry
+    fn open(&mut self, name: &str) -> Result<Box<dyn Read + '_>, MixError>;
+    /// Checks if the source has an entry with the given name
+    fn contains(&self, name: &str) -> bool {
+        self.metadata(name).is_ok()
+    }
+    /// Describes an entry
+    ///
+    /// # Returns
+    /// The metadata, or [`MixError::FileNotFound`] if the source has no such entry
+    fn metadata(&self, name: &str) -> Result<EntryMetadata, MixError>;
+}
+
+/// Description of an entry of an [`ArchiveSource`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryMetadata {
+    /// The file ID of the entry
+    pub id: i32,
+    /// The file name of the entry, if the source knows it
+    pub name: Option<String>,
+    /// The size of the entry in bytes
+    pub size: u64,
+}
+
+/// A plain folder read as an [`ArchiveSource`]
+///
+/// Only the files directly inside the folder are entries, the same files [`crate::patch`] would pack.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+    files: HashMap<String, (String, u64)>,
+}
+
+impl DirectorySource {
+    /// Scans a folder
+    ///
+    /// # Arguments
+    /// * `root` - The folder to read
+    ///
+    /// # Returns
+    /// The source, or [`MixError::NameCollision`] if two file names differ only in case
+    pub fn new(root: &Path) -> Result<Self, MixError> {
+        if !root.is_dir() {
+            return Err(MixError::FileNotFound(root.to_path_buf()));
+        }
+        let mut files = HashMap::new();
+        for entry in std::fs::read_dir(root)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some((other, _)) = files.insert(name.to_ascii_lowercase(), (name.clone(), metadata.len())) {
+                    // Listed in directory order, sorted so the error does not depend on it
+                    let (first, second) = if other < name { (other, name) } else { (name, other) };
+                    return Err(MixError::NameCollision { id: ra2_crc(&first), first, second });
+                }
+            }
+        }
+        Ok(Self { root: root.to_path_buf(), files })
+    }
+    /// The folder being read
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    /// Finds the real file name of an entry
+    fn find(&self, name: &str) -> Result<&(String, u64), MixError> {
+        match self.files.get(&name.to_ascii_lowercase()) {
+            Some(file) => Ok(file),
+            None => Err(MixError::FileNotFound(self.root.join(name))),
+        }
+    }
+}
+
+impl ArchiveSource for DirectorySource {
+    fn list(&self) -> Vec<EntryMetadata> {
+        let mut entries: Vec<EntryMetadata> = self
+            .files
+            .values()
+            .map(|(name, size)| EntryMetadata { id: ra2_crc(name), name: Some(name.clone()), size: *size })
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+    fn open(&mut self, name: &str) -> Result<Box<dyn Read + '_>, MixError> {
+        let (name, _) = self.find(name)?;
+        Ok(Box::new(BufReader::new(File::open(self.root.join(name))?)))
+    }
+    fn metadata(&self, name: &str) -> Result<EntryMetadata, MixError> {
+        let (name, size) = self.find(name)?;
+        Ok(EntryMetadata { id: ra2_crc(name), name: Some(name.clone()), size: *size })
+    }
+}
+
+impl ArchiveSource for MixPackage {
+    fn list(&self) -> Vec<EntryMetadata> {
+        let entries = self.entries_by_id().into_iter();
+        entries.map(|(id, (name, data))| EntryMetadata { id, name: name.map(String::from), size: data.len() as u64 }).collect()
+    }
+    fn open(&mut self, name: &str) -> Result<Box<dyn Read + '_>, MixError> {
+        let (_, data) = find_in_package(self, name)?;
+        Ok(Box::new(data))
+    }
+    fn contains(&self, name: &str) -> bool {
+        find_in_package(self, name).is_ok()
+    }
+    fn metadata(&self, name: &str) -> Result<EntryMetadata, MixError> {
+        let (found, data) = find_in_package(self, name)?;
+        Ok(EntryMetadata { id: ra2_crc(name), name: found.map(String::from), size: data.len() as u64 })
+    }
+}
+
+/// Finds an entry of a package by name, trying the exact name before comparing file IDs
+fn find_in_package<'a>(package: &'a MixPackage, name: &str) -> Result<(Option<&'a str>, &'a [u8]), MixError> {
+    if let Some((found, data)) = package.files.get_key_value(name) {
+        return Ok((Some(found.as_str()), data.as_slice()));
+    }
+    package.entry_by_id(ra2_crc(name)).ok_or_else(|| MixError::FileNotFound(PathBuf::from(name)))
+}
+
+impl<R: Read + Seek> ArchiveSource for MixReader<R> {
+    fn list(&self) -> Vec<EntryMetadata> {
+        let metadata = |entry: &MixEntry| EntryMetadata {
+            id: entry.id,
+            name: self.name(entry.id).map(String::from),
+            size: entry.size.max(0) as u64,
+        };
+        let mut entries: Vec<EntryMetadata> = self.index().entries.iter().map(metadata).collect();
+        entries.sort_by_key(|entry| entry.id);
+        entries
+    }
+    fn open(&mut self, name: &str) -> Result<Box<dyn Read + '_>, MixError> {
+        self.metadata(name)?;
+        Ok(Box::new(self.entry_by_name(name)?))
+    }
+    fn metadata(&self, name: &str) -> Result<EntryMetadata, MixError> {
+        let id = ra2_crc(name);
+        match self.find(id) {
+            Ok(entry) => Ok(EntryMetadata { id, name: self.name(id).map(String::from), size: entry.size.max(0) as u64 }),
+            Err(_) => Err(MixError::FileNotFound(PathBuf::from(name))),
+        }
+    }
+}
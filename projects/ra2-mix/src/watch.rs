@@ -0,0 +1,319 @@
+//! Rebuilds an archive whenever the files of a source tree change
+
+use crate::{
+    CancellationToken, MixError, MixPackage,
+    checksum::{content_hash, ra2_crc},
+};
+use notify::{
+    EventKind, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+    sync::mpsc::{RecvTimeoutError, channel},
+    time::{Duration, Instant},
+};
+
+/// How often [`MixWatcher::watch`] checks its cancellation token while no change arrives
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a rebuild of a [`MixWatcher`] changed in the archive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct WatchSummary {
+    /// The entries added from new source files
+    pub added: Vec<String>,
+    /// The entries replaced because their source file changed
+    pub updated: Vec<String>,
+    /// The entries removed because their source file was deleted
+    pub removed: Vec<String>,
+    /// The size of the written archive in bytes, `None` if nothing changed and the archive was left alone
+    pub written: Option<usize>,
+}
+
+impl WatchSummary {
+    /// Checks if no entry changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Display for WatchSummary {
+    /// Formats the summary on one line, such as `+new.shp ~rulesmd.ini -old.shp (1024 bytes)`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No changes");
+        }
+        let added = self.added.iter().map(|name| format!("+{}", name));
+        let updated = self.updated.iter().map(|name| format!("~{}", name));
+        let removed = self.removed.iter().map(|name| format!("-{}", name));
+        write!(f, "{}", added.chain(updated).chain(removed).collect::<Vec<_>>().join(" "))?;
+        match self.written {
+            Some(size) => write!(f, " ({} bytes)", size),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Keeps an archive in sync with the files of a source tree
+///
+/// Every file of the tree becomes an entry named after the file, whatever folder it is in. Hidden files and folders,
+/// starting with `.`, and backup files ending with `~` are ignored. Entries of the target archive that do not come
+/// from the tree are kept.
+///
+/// # Examples
+/// ```no_run
+/// use ra2_mix::{CancellationToken, MixWatcher};
+/// use std::{path::Path, time::Duration};
+///
+/// let mut watcher = MixWatcher::new(Path::new("mod/src"), Path::new("mod/expandmd01.mix"))
+///     .unwrap()
+///     .with_debounce(Duration::from_millis(500));
+/// watcher
+///     .watch(&CancellationToken::new(), |rebuild| match rebuild {
+///         Ok(summary) => println!("{}", summary),
+///         Err(e) => eprintln!("{}", e),
+///     })
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MixWatcher {
+    source: PathBuf,
+    target: PathBuf,
+    debounce: Duration,
+    package: MixPackage,
+    /// The entries packed from the source tree by file ID, with their name, source file and content hash
+    tracked: BTreeMap<i32, (String, PathBuf, u32)>,
+}
+
+impl MixWatcher {
+    /// Creates a watcher, loading the target archive if it exists
+    ///
+    /// Nothing is written until [`MixWatcher::sync`] or [`MixWatcher::watch`] is called.
+    ///
+    /// # Arguments
+    /// * `source` - The folder holding the files to pack
+    /// * `target` - The archive to write, it may lie inside the source folder
+    pub fn new(source: &Path, target: &Path) -> Result<Self, MixError> {
+        let context = |e: MixError| e.context(format!("Failed to watch `{}`", source.display()));
+        let package = if target.is_file() { MixPackage::load(target).map_err(context)? } else { MixPackage::default() };
+        Ok(Self {
+            source: std::path::absolute(source).map_err(|e| context(e.into()))?,
+            target: std::path::absolute(target).map_err(|e| context(e.into()))?,
+            debounce: Duration::from_millis(200),
+            package,
+            tracked: BTreeMap::new(),
+        })
+    }
+    /// Waits until no change arrived for this long before rebuilding, 200 milliseconds by default
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+    /// The package as last written
+    pub fn package(&self) -> &MixPackage {
+        &self.package
+    }
+    /// Rescans the whole source tree and writes the archive if any entry changed
+    pub fn sync(&mut self) -> Result<WatchSummary, MixError> {
+        let mut files = Vec::new();
+        self.collect_files(&self.source, &mut files)?;
+        files.sort();
+        let mut summary = WatchSummary::default();
+        let stale: Vec<PathBuf> =
+            self.tracked.values().map(|(_, path, _)| path.clone()).filter(|path| !files.contains(path)).collect();
+        for path in stale {
+            self.apply(&path, &mut summary)?;
+        }
+        for path in files {
+            self.apply(&path, &mut summary)?;
+        }
+        self.finish(summary)
+    }
+    /// Applies the changes of some paths of the source tree and writes the archive if any entry changed
+    ///
+    /// Deleted folders remove every entry packed from them, and new folders trigger a full [`MixWatcher::sync`].
+    ///
+    /// # Arguments
+    /// * `paths` - The changed files and folders, paths outside the source tree are ignored
+    pub fn update(&mut self, paths: &[PathBuf]) -> Result<WatchSummary, MixError> {
+        let mut summary = WatchSummary::default();
+        let paths: BTreeSet<PathBuf> = paths
+            .iter()
+            .filter_map(|path| std::path::absolute(path).ok())
+            .filter(|path| self.is_watched(path))
+            .collect();
+        if paths.iter().any(|path| path.is_dir()) {
+            return self.sync();
+        }
+        for path in &paths {
+            self.apply(path, &mut summary)?;
+        }
+        self.finish(summary)
+    }
+    /// Watches the source tree until the token is cancelled, rebuilding the archive after every burst of changes
+    ///
+    /// The tree is synced first. Every rebuild that changes the archive, or fails, is reported to `on_rebuild` and
+    /// watching goes on.
+    ///
+    /// # Arguments
+    /// * `token` - Stops watching when cancelled
+    /// * `on_rebuild` - Called with the outcome of the first sync and of every rebuild
+    ///
+    /// # Returns
+    /// An error if the source tree cannot be watched
+    pub fn watch(
+        &mut self,
+        token: &CancellationToken,
+        mut on_rebuild: impl FnMut(Result<WatchSummary, MixError>),
+    ) -> Result<(), MixError> {
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&self.source, RecursiveMode::Recursive)?;
+        on_rebuild(self.sync());
+        let mut pending = BTreeSet::new();
+        let mut deadline: Option<Instant> = None;
+        while !token.is_cancelled() {
+            let timeout = deadline.map_or(POLL_INTERVAL, |at| at.saturating_duration_since(Instant::now()).min(POLL_INTERVAL));
+            match receiver.recv_timeout(timeout) {
+                Ok(Ok(event)) if is_change(&event.kind) => {
+                    pending.extend(event.paths.into_iter().filter(|path| self.is_watched(path)));
+                    if !pending.is_empty() {
+                        deadline = Some(Instant::now() + self.debounce);
+                    }
+                }
+                Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+                Ok(Err(e)) => on_rebuild(Err(e.into())),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if deadline.is_some_and(|at| Instant::now() >= at) {
+                deadline = None;
+                let paths: Vec<PathBuf> = std::mem::take(&mut pending).into_iter().collect();
+                match self.update(&paths) {
+                    Ok(summary) if summary.is_empty() => {}
+                    rebuild => on_rebuild(rebuild),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds, replaces or removes the entry of a source path
+    fn apply(&mut self, path: &Path, summary: &mut WatchSummary) -> Result<(), MixError> {
+        let data = match path.is_file() {
+            true => match std::fs::read(path) {
+                Ok(data) => Some(data),
+                // Deleted between the check and the read
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(MixError::from(e).context(format!("Failed to read `{}`", path.display()))),
+            },
+            false => None,
+        };
+        let data = match data {
+            Some(data) => data,
+            None => {
+                // A deleted file, or a deleted folder with every file inside
+                let removed: Vec<i32> = self
+                    .tracked
+                    .iter()
+                    .filter(|(_, (_, file, _))| file.starts_with(path))
+                    .map(|(id, _)| *id)
+                    .collect();
+                for id in removed {
+                    if let Some((name, _, _)) = self.tracked.remove(&id) {
+                        self.package.files.retain(|other, _| ra2_crc(other) != id);
+                        summary.removed.push(name);
+                    }
+                }
+                return Ok(());
+            }
+        };
+        let name = path.file_name().and_then(|name| name.to_str()).ok_or(MixError::FileNotFound(path.to_path_buf()))?;
+        let id = ra2_crc(name);
+        let hash = content_hash(&data);
+        match self.tracked.get(&id) {
+            Some((_, file, old)) if file == path => {
+                if *old == hash {
+                    return Ok(());
+                }
+                summary.updated.push(name.to_string());
+            }
+            // Two files of the tree map to the same entry, unless the other one is gone and its event is pending
+            Some((other, file, _)) if file.is_file() => {
+                return Err(MixError::NameCollision { id, first: other.clone(), second: name.to_string() }
+                    .context(format!("`{}` and `{}` map to the same entry", file.display(), path.display())));
+            }
+            _ => summary.added.push(name.to_string()),
+        }
+        // Replaces any entry with the same file ID, such as one kept from the target or named in another case
+        self.package.files.retain(|other, _| ra2_crc(other) != id);
+        self.package.unnamed.remove(&id);
+        self.package.files.insert(name.to_string(), data);
+        self.tracked.insert(id, (name.to_string(), path.to_path_buf(), hash));
+        Ok(())
+    }
+    /// Writes the archive if the summary has changes
+    ///
+    /// The archive is written to a hidden file next to the target and renamed over it, so the game or another tool never
+    /// reads a half written archive and a failed write keeps the previous one.
+    fn finish(&mut self, mut summary: WatchSummary) -> Result<WatchSummary, MixError> {
+        if !summary.is_empty() {
+            let context = |e: MixError| e.context(format!("Failed to write `{}`", self.target.display()));
+            let name = self.target.file_name().unwrap_or_default().to_string_lossy();
+            let temporary = self.target.with_file_name(format!(".{}.tmp", name));
+            let result = self.package.clone().save(&temporary).and_then(|size| {
+                std::fs::rename(&temporary, &self.target)?;
+                Ok(size)
+            });
+            if result.is_err() {
+                let _ = std::fs::remove_file(&temporary);
+            }
+            summary.written = Some(result.map_err(context)?);
+        }
+        Ok(summary)
+    }
+    /// Checks if a path belongs to the source tree and is not ignored
+    fn is_watched(&self, path: &Path) -> bool {
+        let relative = match path.strip_prefix(&self.source) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        path != self.target
+            && relative.components().all(|component| {
+                let name = component.as_os_str().to_string_lossy();
+                !name.starts_with('.') && !name.ends_with('~')
+            })
+    }
+    /// Collects the files of a folder recursively, skipping ignored ones
+    ///
+    /// Links to folders are not followed, so a link cycle cannot recurse forever and folders outside the tree are not
+    /// packed, links to files are.
+    fn collect_files(&self, folder: &Path, files: &mut Vec<PathBuf>) -> Result<(), MixError> {
+        for entry in std::fs::read_dir(folder)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !self.is_watched(&path) {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.collect_files(&path, files)?;
+            }
+            else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks if an event may change the content of files, reads and opens do not
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        _ => true,
+    }
+}
@@ -0,0 +1,236 @@
+//! Async reading and writing of RA2 MIX files on top of tokio
+//!
+//! The reader only loads the header and index up front and reads entries on demand, so large archives can be
+//! browsed without holding them in memory.
+
+use super::{
+    index::{MixIndex, needed_length},
+    stream::{entry_positions, find_entry},
+    reader::{ResolvedNames, database_range, out_of_bounds, resolve_names},
+    writer::EncodedMix,
+    *,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, Take};
+
+/// Reads a MIX file entry by entry from an async source
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{AsyncMixReader, MixPackage, ra2_crc};
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut mix = MixPackage::default();
+/// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+/// let data = mix.encode().unwrap();
+///
+/// let mut reader = AsyncMixReader::open(std::io::Cursor::new(data)).await.unwrap();
+/// assert_eq!(reader.name(ra2_crc("hello.txt")), Some("hello.txt"));
+/// assert_eq!(reader.read_entry(ra2_crc("hello.txt")).await.unwrap(), b"Hello, World!");
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AsyncMixReader<R> {
+    reader: R,
+    index: MixIndex,
+    positions: HashMap<i32, usize>,
+    body_size: u64,
+    resolved: ResolvedNames,
+    warnings: Vec<MixWarning>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncMixReader<R> {
+    /// Reads the header, index and local mix database of a MIX file
+    ///
+    /// # Arguments
+    /// * `reader` - The source of the MIX file, positioned anywhere
+    pub async fn open(mut reader: R) -> Result<Self, MixError> {
+        let length = reader.seek(SeekFrom::End(0)).await?;
+        reader.seek(SeekFrom::Start(0)).await?;
+        let index = read_index(&mut reader).await?;
+        let body_size = length.saturating_sub(index.body_offset);
+        MixError::check_bounds(index.body_offset as usize, 0, length as usize)?;
+
+        let mut this = Self {
+            reader,
+            positions: entry_positions(&index),
+            index,
+            body_size,
+            resolved: ResolvedNames { game: XccGame::default(), database: None, names: HashMap::new() },
+            warnings: Vec::new(),
+        };
+        let database = match database_range(&this.index, body_size, &mut this.warnings) {
+            Some(_) => Some(this.read_entry(ra2_crc(MIX_DB_FILENAME)).await?),
+            None => None,
+        };
+        this.resolved = resolve_names(&this.index.entries, database.as_deref(), &mut this.warnings)?;
+        Ok(this)
+    }
+    /// The header and index of the archive
+    pub fn index(&self) -> &MixIndex {
+        &self.index
+    }
+    /// The game of the archive, read from the local mix database or guessed from the entries
+    pub fn game(&self) -> XccGame {
+        self.resolved.game
+    }
+    /// The local mix database of the archive, if it has one
+    pub fn database(&self) -> Option<&LocalMixDatabase> {
+        self.resolved.database.as_ref()
+    }
+    /// The warnings reported while opening the archive
+    pub fn warnings(&self) -> &[MixWarning] {
+        &self.warnings
+    }
+    /// The file name of an entry, if the archive knows it
+    pub fn name(&self, id: i32) -> Option<&str> {
+        self.resolved.names.get(&id).map(|name| name.as_str())
+    }
+    /// Reads the whole data of an entry
+    ///
+    /// # Arguments
+    /// * `id` - The file ID of the entry
+    ///
+    /// # Returns
+    /// The entry data, or [`MixError::InvalidIndex`] if the entry lies outside the body
+    pub async fn read_entry(&mut self, id: i32) -> Result<Vec<u8>, MixError> {
+        let mut data = Vec::new();
+        self.entry_reader(id).await?.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+    /// Streams the data of an entry without loading it in memory
+    ///
+    /// # Arguments
+    /// * `id` - The file ID of the entry
+    ///
+    /// # Returns
+    /// A reader that ends with the entry
+    pub async fn entry_reader(&mut self, id: i32) -> Result<Take<&mut R>, MixError> {
+        let entry = *find_entry(&self.index, &self.positions, id)?;
+        let (start, size) = entry.range(self.body_size)?;
+        self.range_reader(start, size).await
+    }
+    /// Streams `size` bytes of the body from `start`
+    async fn range_reader(&mut self, start: u64, size: u64) -> Result<Take<&mut R>, MixError> {
+        self.reader.seek(SeekFrom::Start(self.index.body_offset + start)).await?;
+        Ok((&mut self.reader).take(size))
+    }
+    /// Reads every entry into a package, in body order
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    pub async fn into_package(mut self) -> Result<(MixPackage, Vec<MixWarning>), MixError> {
+        let mix_db_id = ra2_crc(MIX_DB_FILENAME);
+        let mut entries = self.index.entries.clone();
+        entries.sort_by_key(|entry| entry.offset);
+
+        let mut files = HashMap::new();
+        let mut unnamed = HashMap::new();
+        for entry in entries.iter().filter(|entry| entry.id != mix_db_id) {
+            // Every entry is read from its own range, looking it up by ID would find the first of duplicate IDs
+            let data = match entry.range(self.body_size) {
+                Ok((start, size)) => {
+                    let mut data = Vec::with_capacity(size as usize);
+                    self.range_reader(start, size).await?.read_to_end(&mut data).await?;
+                    data
+                }
+                Err(_) => {
+                    report(&mut self.warnings, out_of_bounds(entry));
+                    Vec::new()
+                }
+            };
+            match self.resolved.names.get(&entry.id) {
+                Some(filename) => files.insert(filename.clone(), data),
+                None => unnamed.insert(entry.id, data),
+            };
+        }
+
+        if self.resolved.database.is_some() && !unnamed.is_empty() {
+            report(&mut self.warnings, MixWarning::UnnamedEntries { count: unnamed.len() });
+        }
+        let ResolvedNames { game, database, .. } = self.resolved;
+        let checksum = self.index.header.has_checksum();
+        let package = MixPackage {
+            game,
+            files,
+            unnamed,
+            database,
+            database_policy: Default::default(),
+            index: Some(self.index),
+            layout: Default::default(),
+            checksum,
+        };
+        Ok((package, self.warnings))
+    }
+}
+
+/// Reads the header and index from the start of an async reader, consuming nothing past the index
+async fn read_index<R: AsyncRead + Unpin>(reader: &mut R) -> Result<MixIndex, MixError> {
+    let mut prefix = Vec::new();
+    loop {
+        let result = MixIndex::parse(&prefix);
+        let length = match needed_length(&result, prefix.len()) {
+            Some(length) => length,
+            None => return result,
+        };
+        let missing = (length - prefix.len()) as u64;
+        if (&mut *reader).take(missing).read_to_end(&mut prefix).await? < missing as usize {
+            return result;
+        }
+    }
+}
+
+impl MixPackage {
+    /// Reads a MIX file from disk without blocking the runtime
+    ///
+    /// # Arguments
+    /// * `mix_path` - Path to the MIX file
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    pub async fn load_async(mix_path: &Path) -> Result<(Self, Vec<MixWarning>), MixError> {
+        let context = |e: MixError| e.context(format!("Failed to load `{}`", mix_path.display()));
+        let file = tokio::fs::File::open(mix_path).await.map_err(|e| context(e.into()))?;
+        let reader = AsyncMixReader::open(tokio::io::BufReader::new(file)).await.map_err(context)?;
+        reader.into_package().await.map_err(context)
+    }
+    /// Writes the package to disk without blocking the runtime
+    ///
+    /// # Arguments
+    /// * `output` - Path of the MIX file to write
+    ///
+    /// # Returns
+    /// The size of the written file in bytes
+    pub async fn save_async(self, output: &Path) -> Result<usize, MixError> {
+        let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(output).await?);
+        let size = self.write_async(&mut file).await?;
+        file.shutdown().await?;
+        Ok(size)
+    }
+    /// Streams the encoded package to an async writer, entry by entry
+    ///
+    /// # Arguments
+    /// * `writer` - The destination of the MIX file
+    ///
+    /// # Returns
+    /// The number of bytes written
+    pub async fn write_async<W: AsyncWrite + Unpin>(self, writer: &mut W) -> Result<usize, MixError> {
+        let encoded = self.encode_index(&mut MixTask::new())?;
+        let digest = encoded.digest();
+        let EncodedMix { head, bodies, tail, .. } = encoded;
+        writer.write_all(&head).await?;
+        let mut size = head.len();
+        for (gap, data) in &bodies {
+            writer.write_all(&vec![0; *gap]).await?;
+            writer.write_all(data).await?;
+            size += gap + data.len();
+        }
+        writer.write_all(&vec![0; tail]).await?;
+        size += tail;
+        if let Some(digest) = digest {
+            writer.write_all(&digest).await?;
+            size += digest.len();
+        }
+        writer.flush().await?;
+        Ok(size)
+    }
+}
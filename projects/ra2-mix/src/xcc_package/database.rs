@@ -0,0 +1,124 @@
+//! Local mix database (LMD) support for RA2 MIX files
+
+use super::*;
+
+/// Local mix database, the XCC file that stores the names of the files in a MIX archive
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalMixDatabase {
+    /// The game recorded in the XCC header
+    pub game: XccGame,
+    /// The file names stored in the database, in file order
+    pub names: Vec<String>,
+}
+
+/// Policy for writing the local mix database when a package is encoded
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "manifest", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum DatabasePolicy {
+    /// Build a new database from the sorted names of the files in the package
+    #[default]
+    Regenerate,
+    /// Write the database read from the archive untouched, or regenerate it if there was none
+    ///
+    /// Encoding fails with [`MixError::Unsupported`] if the database does not name every file, since the missing
+    /// files would read back as unnamed; use [`DatabasePolicy::Merge`] to add names.
+    Preserve,
+    /// Keep the names of the database read from the archive and add the names it is missing, sorted like
+    /// [`DatabasePolicy::Regenerate`]
+    Merge,
+    /// Do not write a database at all
+    Omit,
+}
+
+impl LocalMixDatabase {
+    /// Parses a local mix database from its raw bytes
+    ///
+    /// # Arguments
+    /// * `data` - The bytes of the `local mix database.dat` entry
+    ///
+    /// # Returns
+    /// The database with its game tag and file names, or an error if the XCC header is malformed
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{LocalMixDatabase, XccGame};
+    ///
+    /// let lmd = LocalMixDatabase { game: XccGame::RA2_YR, names: vec!["rulesmd.ini".to_string()] };
+    /// let decoded = LocalMixDatabase::decode(&lmd.encode()).unwrap();
+    /// assert_eq!(decoded, lmd);
+    /// ```
+    pub fn decode(data: &[u8]) -> Result<Self, MixError> {
+        MixError::check_bounds(0, XCC_HEADER_SIZE, data.len())?;
+        if !data.starts_with(XCC_ID_BYTES) {
+            return Err(MixError::InvalidFormat("Local mix database has no XCC signature".to_string()));
+        }
+
+        let mut cursor = std::io::Cursor::new(&data[32..XCC_HEADER_SIZE]);
+        let _size = cursor.read_u32::<LittleEndian>()?;
+        let _file_type = cursor.read_u32::<LittleEndian>()?;
+        let _version = cursor.read_u32::<LittleEndian>()?;
+        let game = XccGame::from(cursor.read_u32::<LittleEndian>()?);
+        let _count = cursor.read_u32::<LittleEndian>()?;
+
+        let mut names = Vec::new();
+        for name in data[XCC_HEADER_SIZE..].split(|byte| *byte == 0) {
+            if name.is_empty() {
+                continue;
+            }
+            if let Ok(name) = std::str::from_utf8(name) {
+                names.push(name.to_string());
+            }
+        }
+
+        Ok(Self { game, names })
+    }
+
+    /// Serializes the database into the bytes of a `local mix database.dat` entry
+    pub fn encode(&self) -> Vec<u8> {
+        let num_files = self.names.len();
+        let db_size_in_bytes = XCC_HEADER_SIZE + self.names.iter().map(|filename| filename.len() + 1).sum::<usize>();
+
+        let mut bytes_data = Vec::with_capacity(db_size_in_bytes);
+
+        // Write XCC ID bytes
+        bytes_data.extend_from_slice(XCC_ID_BYTES);
+        // Pad to 32 bytes
+        bytes_data.resize(32, 0);
+
+        // Write header fields
+        bytes_data.write_u32::<LittleEndian>(db_size_in_bytes as u32).unwrap();
+        bytes_data.write_u32::<LittleEndian>(XCC_FILE_TYPE).unwrap();
+        bytes_data.write_u32::<LittleEndian>(XCC_FILE_VERSION).unwrap();
+        bytes_data.write_u32::<LittleEndian>(self.game as u32).unwrap();
+        bytes_data.write_u32::<LittleEndian>(num_files as u32).unwrap();
+
+        // Write filenames with null terminators
+        for filename in &self.names {
+            bytes_data.extend_from_slice(filename.as_bytes());
+            bytes_data.push(0); // Null terminator
+        }
+
+        bytes_data
+    }
+}
+
+/// Guesses the game of an archive without a database from the IDs of well-known files it contains
+///
+/// # Arguments
+/// * `ids` - The file IDs of every entry in the archive
+///
+/// # Returns
+/// The detected game, or `None` if no signature file was found
+pub fn detect_game(ids: &[i32]) -> Option<XccGame> {
+    let contains_any = |names: &[&str]| names.iter().any(|name| ids.contains(&ra2_crc(name)));
+    if contains_any(RA2_YR_SIGNATURE_FILES) {
+        Some(XccGame::RA2_YR)
+    }
+    else if contains_any(RA2_SIGNATURE_FILES) {
+        Some(XccGame::RA2)
+    }
+    else {
+        None
+    }
+}
@@ -0,0 +1,347 @@
+//! Structural diff between two RA2 MIX packages
+
+use super::*;
+use crate::checksum::content_hash;
+use std::ops::Range;
+
+/// File extensions whose entries are compared line by line
+const TEXT_EXTENSIONS: &[&str] = &["ini", "txt", "csv", "map", "mpr"];
+
+/// Kind of change of an entry between two packages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// The entry only exists in the new package
+    Added,
+    /// The entry only exists in the old package
+    Removed,
+    /// The entry exists in both packages with different contents
+    Modified,
+}
+
+/// A line that differs between the old and the new version of a text entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineChange {
+    /// A line of the new entry, with its 1-based line number in the new entry
+    Added {
+        /// The line number in the new entry
+        line: usize,
+        /// The text of the line
+        text: String,
+    },
+    /// A line of the old entry, with its 1-based line number in the old entry
+    Removed {
+        /// The line number in the old entry
+        line: usize,
+        /// The text of the line
+        text: String,
+    },
+}
+
+/// An entry that differs between two packages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryChange {
+    /// The file ID of the entry
+    pub id: i32,
+    /// The file name of the entry, if either package knows it
+    pub name: Option<String>,
+    /// How the entry changed
+    pub kind: ChangeKind,
+    /// The size of the entry in the old package
+    pub old_size: Option<u64>,
+    /// The size of the entry in the new package
+    pub new_size: Option<u64>,
+    /// The content hash of the entry in the old package
+    pub old_hash: Option<u32>,
+    /// The content hash of the entry in the new package
+    pub new_hash: Option<u32>,
+    /// The changed lines of a modified INI, CSF or other text entry
+    pub lines: Option<Vec<LineChange>>,
+}
+
+impl EntryChange {
+    /// The size difference between the new and the old entry in bytes
+    pub fn size_delta(&self) -> i64 {
+        self.new_size.unwrap_or(0) as i64 - self.old_size.unwrap_or(0) as i64
+    }
+}
+
+/// Differences between two MIX packages, matched by file ID
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MixDiff {
+    /// Every added, removed or modified entry, ordered by file ID
+    pub changes: Vec<EntryChange>,
+}
+
+impl MixDiff {
+    /// Compares two packages entry by entry
+    ///
+    /// # Arguments
+    /// * `old` - The previous version of the archive
+    /// * `new` - The updated version of the archive
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{ChangeKind, MixDiff, MixPackage};
+    ///
+    /// let mut old = MixPackage::default();
+    /// old.add_any("rules.ini".to_string(), b"[General]\nSpeed=1\n".to_vec());
+    /// let mut new = MixPackage::default();
+    /// new.add_any("rules.ini".to_string(), b"[General]\nSpeed=2\n".to_vec());
+    ///
+    /// let diff = MixDiff::compare(&old, &new);
+    /// assert_eq!(diff.changes[0].kind, ChangeKind::Modified);
+    /// assert_eq!(diff.changes[0].lines.as_ref().unwrap().len(), 2);
+    /// ```
+    pub fn compare(old: &MixPackage, new: &MixPackage) -> Self {
+        let old_entries = old.entries_by_id();
+        let new_entries = new.entries_by_id();
+        let mut ids: Vec<i32> = old_entries.keys().chain(new_entries.keys()).copied().collect();
+        ids.sort();
+        ids.dedup();
+
+        // Hashing and line diffs run concurrently with the `parallel` feature
+        let changes = par_map(&ids, |id| {
+            let old_entry = old_entries.get(id);
+            let new_entry = new_entries.get(id);
+            let name = new_entry.and_then(|(name, _)| *name).or_else(|| old_entry.and_then(|(name, _)| *name));
+            let kind = match (old_entry, new_entry) {
+                (Some((_, old_data)), Some((_, new_data))) if old_data == new_data => return None,
+                (Some(_), Some(_)) => ChangeKind::Modified,
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (None, None) => return None,
+            };
+            let lines = match (old_entry, new_entry, name) {
+                (Some((_, old_data)), Some((_, new_data)), Some(name)) => diff_text(name, old_data, new_data),
+                _ => None,
+            };
+            Some(EntryChange {
+                id: *id,
+                name: name.map(str::to_string),
+                kind,
+                old_size: old_entry.map(|(_, data)| data.len() as u64),
+                new_size: new_entry.map(|(_, data)| data.len() as u64),
+                old_hash: old_entry.map(|(_, data)| content_hash(data)),
+                new_hash: new_entry.map(|(_, data)| content_hash(data)),
+                lines,
+            })
+        });
+        let changes = changes.into_iter().flatten().collect();
+        Self { changes }
+    }
+
+    /// Builds a minimal patch package holding only the added and modified entries of the new package
+    ///
+    /// Removed entries cannot be expressed in a MIX archive and are left out.
+    ///
+    /// # Arguments
+    /// * `new` - The package the diff was compared against
+    pub fn patch(&self, new: &MixPackage) -> MixPackage {
+        let mut patch = MixPackage { game: new.game, ..Default::default() };
+        let new_entries = new.entries_by_id();
+        for change in self.changes.iter().filter(|change| change.kind != ChangeKind::Removed) {
+            match new_entries.get(&change.id) {
+                Some((Some(name), data)) => patch.files.insert(name.to_string(), data.to_vec()),
+                Some((None, data)) => patch.unnamed.insert(change.id, data.to_vec()),
+                None => None,
+            };
+        }
+        patch
+    }
+}
+
+/// Compares the lines of two versions of a text or string table entry
+fn diff_text(name: &str, old: &[u8], new: &[u8]) -> Option<Vec<LineChange>> {
+    let extension = name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase())?;
+    let (old_lines, new_lines) = if extension == "csf" {
+        (csf_lines(old)?, csf_lines(new)?)
+    }
+    else if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+        (text_lines(old), text_lines(new))
+    }
+    else {
+        return None;
+    };
+    Some(diff_lines(&old_lines, &new_lines))
+}
+
+/// Splits a text entry into lines
+fn text_lines(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data).lines().map(str::to_string).collect()
+}
+
+/// Decodes a CSF string table into `LABEL=value` lines
+fn csf_lines(data: &[u8]) -> Option<Vec<String>> {
+    let mut cursor = std::io::Cursor::new(data);
+    let mut tag = [0u8; 4];
+    std::io::Read::read_exact(&mut cursor, &mut tag).ok()?;
+    if &tag != b" FSC" {
+        return None;
+    }
+    let _version = cursor.read_u32::<LittleEndian>().ok()?;
+    let labels = cursor.read_u32::<LittleEndian>().ok()?;
+    let _strings = cursor.read_u32::<LittleEndian>().ok()?;
+    let _unused = cursor.read_u32::<LittleEndian>().ok()?;
+    let _language = cursor.read_u32::<LittleEndian>().ok()?;
+
+    let read_bytes = |cursor: &mut std::io::Cursor<&[u8]>, length: usize| {
+        let start = cursor.position() as usize;
+        let bytes = data.get(start..start.checked_add(length)?)?;
+        cursor.set_position((start + length) as u64);
+        Some(bytes.to_vec())
+    };
+    let mut lines = Vec::new();
+    for _ in 0..labels {
+        if read_bytes(&mut cursor, 4)? != b" LBL" {
+            return None;
+        }
+        let pairs = cursor.read_u32::<LittleEndian>().ok()?;
+        let label_length = cursor.read_u32::<LittleEndian>().ok()? as usize;
+        let label = String::from_utf8_lossy(&read_bytes(&mut cursor, label_length)?).into_owned();
+        for _ in 0..pairs {
+            let kind = read_bytes(&mut cursor, 4)?;
+            let value_length = cursor.read_u32::<LittleEndian>().ok()? as usize;
+            let value: Vec<u16> = read_bytes(&mut cursor, value_length * 2)?
+                .chunks_exact(2)
+                .map(|pair| !u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            let value = String::from_utf16_lossy(&value).replace('\n', "\\n");
+            lines.push(format!("{}={}", label, value));
+            if kind == b"WRTS" {
+                let extra_length = cursor.read_u32::<LittleEndian>().ok()? as usize;
+                read_bytes(&mut cursor, extra_length)?;
+            }
+        }
+    }
+    Some(lines)
+}
+
+/// The edit distance after which a middle snake search gives up and the range is replaced as a whole
+///
+/// The search takes time proportional to the lines times the distance, this bounds it for unrelated texts.
+const MAX_EDIT_COST: usize = 1024;
+
+/// Computes the changed lines between two texts with the linear space variant of the Myers algorithm
+///
+/// Memory stays proportional to the number of lines however many lines differ, so fully rewritten files are fine.
+fn diff_lines(old: &[String], new: &[String]) -> Vec<LineChange> {
+    // Both diagonal arrays cover every edit distance the middle snake search can reach
+    let max = (old.len() + new.len()).div_ceil(2).min(MAX_EDIT_COST) + 1;
+    let mut search = SnakeSearch { forward: vec![0; 2 * max + 1], backward: vec![0; 2 * max + 1], offset: max as isize };
+    let mut edits = Vec::new();
+    search.diff(old, 0..old.len(), new, 0..new.len(), &mut edits);
+
+    // Lists the removed lines of every hunk before its added lines, the split can leave them interleaved
+    let mut changes = Vec::with_capacity(edits.len());
+    let mut hunk: Vec<LineChange> = Vec::new();
+    let mut end = None;
+    for (start, change) in edits {
+        if end != Some(start) {
+            hunk.sort_by_key(|change| matches!(change, LineChange::Added { .. }));
+            changes.append(&mut hunk);
+        }
+        end = Some(match change {
+            LineChange::Removed { .. } => (start.0 + 1, start.1),
+            LineChange::Added { .. } => (start.0, start.1 + 1),
+        });
+        hunk.push(change);
+    }
+    hunk.sort_by_key(|change| matches!(change, LineChange::Added { .. }));
+    changes.append(&mut hunk);
+    changes
+}
+
+/// A changed line with the positions in the old and new texts where it starts
+type Edit = ((usize, usize), LineChange);
+
+/// The diagonal arrays of the forward and backward searches, indexed by diagonal `k = x - y` plus the offset
+struct SnakeSearch {
+    forward: Vec<usize>,
+    backward: Vec<usize>,
+    offset: isize,
+}
+
+impl SnakeSearch {
+    /// Appends the changes between two line ranges, splitting them at their middle snake
+    fn diff(&mut self, a: &[String], old: Range<usize>, b: &[String], new: Range<usize>, edits: &mut Vec<Edit>) {
+        let (mut old, mut new) = (old, new);
+        // Skip the common prefix and suffix to keep the search small
+        while old.start < old.end && new.start < new.end && a[old.start] == b[new.start] {
+            old.start += 1;
+            new.start += 1;
+        }
+        while old.start < old.end && new.start < new.end && a[old.end - 1] == b[new.end - 1] {
+            old.end -= 1;
+            new.end -= 1;
+        }
+        let split = match old.is_empty() || new.is_empty() {
+            true => None,
+            false => self.middle_snake(a, old.clone(), b, new.clone()),
+        };
+        match split {
+            Some((x, y)) => {
+                self.diff(a, old.start..x, b, new.start..y, edits);
+                self.diff(a, x..old.end, b, y..new.end, edits);
+            }
+            None => {
+                let (x, y) = (old.start, new.start);
+                edits.extend(old.map(|x| ((x, y), LineChange::Removed { line: x + 1, text: a[x].clone() })));
+                edits.extend(new.map(|y| ((x, y), LineChange::Added { line: y + 1, text: b[y].clone() })));
+            }
+        }
+    }
+
+    /// Finds where the forward and backward searches meet, the point to split the ranges at
+    ///
+    /// The ranges differ at both ends, so the point always lies strictly inside them. Returns `None` once the
+    /// distance exceeds [`MAX_EDIT_COST`].
+    fn middle_snake(&mut self, a: &[String], old: Range<usize>, b: &[String], new: Range<usize>) -> Option<(usize, usize)> {
+        let (n, m) = (old.len(), new.len());
+        let delta = n as isize - m as isize;
+        let odd = delta % 2 != 0;
+        let at = |k: isize| (k + self.offset) as usize;
+        self.forward[at(1)] = 0;
+        self.backward[at(1)] = 0;
+        for d in 0..=((n + m).div_ceil(2).min(MAX_EDIT_COST) as isize) {
+            for k in (-d..=d).rev().step_by(2) {
+                let forward = &mut self.forward;
+                let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                    forward[at(k + 1)]
+                }
+                else {
+                    forward[at(k - 1)] + 1
+                };
+                let (start_x, start_y) = (x, (x as isize - k) as usize);
+                let mut y = start_y;
+                while x < n && y < m && a[old.start + x] == b[new.start + y] {
+                    x += 1;
+                    y += 1;
+                }
+                forward[at(k)] = x;
+                if odd && (k - delta).abs() < d && x + self.backward[at(delta - k)] >= n {
+                    return Some((old.start + start_x, new.start + start_y));
+                }
+            }
+            for k in (-d..=d).rev().step_by(2) {
+                let backward = &mut self.backward;
+                let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                    backward[at(k + 1)]
+                }
+                else {
+                    backward[at(k - 1)] + 1
+                };
+                let mut y = (x as isize - k) as usize;
+                while x < n && y < m && a[old.end - x - 1] == b[new.end - y - 1] {
+                    x += 1;
+                    y += 1;
+                }
+                backward[at(k)] = x;
+                if !odd && (k - delta).abs() <= d && x + self.forward[at(delta - k)] >= n {
+                    return Some((old.end - x, new.end - y));
+                }
+            }
+        }
+        // The searches meet within half the total length, so only the cost limit ends up here
+        None
+    }
+}
@@ -0,0 +1,215 @@
+//! Header and index parsing for RA2 MIX files
+
+use super::*;
+use sha1::{Digest, Sha1};
+use std::io::Read;
+
+/// Header and index of a MIX file, everything that precedes the body
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct MixIndex {
+    /// The decoded header, with the real file count and data size of encrypted archives
+    pub header: MixHeader,
+    /// The index entries in file order
+    pub entries: Vec<MixEntry>,
+    /// The offset of the body from the start of the file
+    pub body_offset: u64,
+}
+
+impl MixIndex {
+    /// Parses the header and index from the start of a MIX file, decrypting them if needed
+    ///
+    /// # Arguments
+    /// * `data` - The bytes of the MIX file, the body may be left out
+    ///
+    /// # Returns
+    /// The index, or [`MixError::Truncated`] if the data ends before the index does
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{MixIndex, MixPackage};
+    ///
+    /// let mut mix = MixPackage::default();
+    /// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+    /// let index = MixIndex::parse(&mix.encode().unwrap()).unwrap();
+    /// assert_eq!(index.entries.len(), 2);
+    /// assert_eq!(index.body_offset, 10 + 2 * 12);
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Self, MixError> {
+        MixError::check_bounds(0, MIN_HEADER_SIZE, data.len())?;
+        let mut cursor = std::io::Cursor::new(data);
+
+        // Check if this is an old format MIX file
+        let first_word = cursor.read_u16::<LittleEndian>()?;
+        cursor.seek(SeekFrom::Start(0))?; // Reset cursor position
+
+        let mut header: MixHeader;
+        let header_size: usize;
+
+        if first_word != 0 {
+            // Old format
+            let count = cursor.read_u16::<LittleEndian>()?;
+            let size = cursor.read_u32::<LittleEndian>()?;
+            header = MixHeader { flags: None, file_count: count, data_size: size };
+            header_size = MIN_HEADER_SIZE;
+        }
+        else {
+            // New format
+            MixError::check_bounds(0, HEADER_SIZE, data.len())?;
+            let flags = cursor.read_u32::<LittleEndian>()?;
+            let count = cursor.read_u16::<LittleEndian>()?;
+            let size = cursor.read_u32::<LittleEndian>()?;
+            header = MixHeader { flags: Some(flags), file_count: count, data_size: size };
+            header_size = HEADER_SIZE;
+        }
+
+        let entries: Vec<MixEntry>;
+        let mut body_offset = header_size + header.file_count as usize * FILE_ENTRY_SIZE;
+
+        if header.is_encrypted() {
+            // Handle encrypted header
+            let encrypted_key_start = SIZE_OF_FLAGS;
+            let encrypted_key_end = encrypted_key_start + SIZE_OF_ENCRYPTED_KEY;
+            MixError::check_bounds(encrypted_key_start, SIZE_OF_ENCRYPTED_KEY, data.len())?;
+
+            let encrypted_blowfish_key = &data[encrypted_key_start..encrypted_key_end];
+            let decrypted_blowfish_key = decrypt_blowfish_key(encrypted_blowfish_key)?;
+
+            let (file_count, data_size, index_data) = decrypt_mix_header(data, &decrypted_blowfish_key)?;
+
+            entries = get_file_entries(file_count as usize, &index_data)?;
+            header.file_count = file_count;
+            header.data_size = data_size;
+            body_offset = HEADER_SIZE
+                + FILE_ENTRY_SIZE * file_count as usize
+                + SIZE_OF_ENCRYPTED_KEY
+                + get_decryption_block_sizing(file_count).1;
+        }
+        else {
+            // Handle unencrypted header
+            let index_start = header_size;
+            let index_end = body_offset;
+            MixError::check_bounds(index_start, index_end - index_start, data.len())?;
+
+            entries = get_file_entries(header.file_count as usize, &data[index_start..index_end])?;
+        }
+
+        Ok(Self { header, entries, body_offset: body_offset as u64 })
+    }
+
+    /// Reads the header and index from a reader positioned at the start of a MIX file
+    ///
+    /// Only the bytes that precede the body are consumed.
+    ///
+    /// # Arguments
+    /// * `reader` - The source of the MIX file
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, MixError> {
+        let mut prefix = Vec::new();
+        loop {
+            let result = MixIndex::parse(&prefix);
+            let length = match needed_length(&result, prefix.len()) {
+                Some(length) => length,
+                None => return result,
+            };
+            let missing = (length - prefix.len()) as u64;
+            if reader.by_ref().take(missing).read_to_end(&mut prefix)? < missing as usize {
+                return result;
+            }
+        }
+    }
+
+    /// Finds the first entry with the given file ID
+    ///
+    /// This scans the index, [`MixReader`](crate::MixReader) keeps a map of the entries for repeated lookups.
+    pub fn entry(&self, id: i32) -> Option<&MixEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+    /// Checks the SHA-1 digest that follows the body of a checksummed archive
+    ///
+    /// # Arguments
+    /// * `data` - The bytes of the whole MIX file
+    ///
+    /// # Returns
+    /// Whether the digest matches the body, or `None` if the archive has no checksum
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{MixIndex, MixPackage};
+    ///
+    /// let mut mix = MixPackage { checksum: true, ..Default::default() };
+    /// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+    /// let mut data = mix.encode().unwrap();
+    /// let index = MixIndex::parse(&data).unwrap();
+    /// assert_eq!(index.verify_checksum(&data), Some(true));
+    /// data[index.body_offset as usize] ^= 1;
+    /// assert_eq!(index.verify_checksum(&data), Some(false));
+    /// ```
+    pub fn verify_checksum(&self, data: &[u8]) -> Option<bool> {
+        if !self.header.has_checksum() {
+            return None;
+        }
+        let start = self.body_offset as usize;
+        let end = start + self.header.data_size as usize;
+        match (data.get(start..end), data.get(end..end + CHECKSUM_SIZE)) {
+            (Some(body), Some(digest)) => Some(Sha1::digest(body).as_slice() == digest),
+            _ => Some(false),
+        }
+    }
+}
+
+impl MixEntry {
+    /// The start and size of the entry in a body of `body_size` bytes
+    ///
+    /// # Returns
+    /// The range, or [`MixError::InvalidIndex`] if the entry lies outside the body
+    pub fn range(&self, body_size: u64) -> Result<(u64, u64), MixError> {
+        let invalid = || MixError::InvalidIndex {
+            id: self.id,
+            offset: self.offset as i64,
+            size: self.size as i64,
+            body_size,
+        };
+        let start = u64::try_from(self.offset).map_err(|_| invalid())?;
+        let size = u64::try_from(self.size).map_err(|_| invalid())?;
+        if start + size > body_size {
+            return Err(invalid());
+        }
+        Ok((start, size))
+    }
+}
+
+impl MixHeader {
+    /// Checks if the header and index are encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.flags.is_some_and(|flags| flags & FLAG_ENCRYPTED != 0)
+    }
+    /// Checks if a SHA-1 digest of the body follows the body
+    pub fn has_checksum(&self) -> bool {
+        self.flags.is_some_and(|flags| flags & FLAG_CHECKSUM != 0)
+    }
+}
+
+/// The length the data must reach for an incomplete index to parse, or `None` if more data will not help
+pub(crate) fn needed_length(result: &Result<MixIndex, MixError>, length: usize) -> Option<usize> {
+    match result {
+        Err(MixError::Truncated { offset, needed, .. }) if (offset + needed) as usize > length => {
+            Some((offset + needed) as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Parses file entries from index data
+fn get_file_entries(file_count: usize, index_data: &[u8]) -> Result<Vec<MixEntry>, MixError> {
+    let mut file_entries = Vec::with_capacity(file_count);
+    let mut cursor = std::io::Cursor::new(index_data);
+
+    for _ in 0..file_count {
+        let id = cursor.read_i32::<LittleEndian>()?;
+        let offset = cursor.read_i32::<LittleEndian>()?;
+        let size = cursor.read_i32::<LittleEndian>()?;
+        file_entries.push(MixEntry { id, offset, size });
+    }
+
+    Ok(file_entries)
+}
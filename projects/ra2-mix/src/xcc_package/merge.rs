@@ -0,0 +1,158 @@
+//! Merging several RA2 MIX packages into one
+
+use super::*;
+use std::collections::btree_map::Entry;
+
+/// Policy for resolving entries that exist in more than one package with different contents
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the entry of the first package that has it
+    #[default]
+    FirstWins,
+    /// Keep the entry of the last package that has it
+    LastWins,
+    /// Fail with [`MixError::EntryConflict`]
+    Error,
+}
+
+/// An entry that exists in more than one merged package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// The file ID of the entry
+    pub id: i32,
+    /// The file name of the entry, if any package knows it
+    pub name: Option<String>,
+    /// The indices of the packages that contain the entry, in merge order
+    pub sources: Vec<usize>,
+    /// The index of the package whose entry was kept
+    pub winner: usize,
+    /// Whether every copy of the entry has the same contents
+    pub identical: bool,
+}
+
+/// Report of the entries that were resolved while merging
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Every entry found in more than one package, ordered by file ID
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// An entry candidate while merging
+struct MergeEntry {
+    name: Option<String>,
+    data: Vec<u8>,
+    sources: Vec<usize>,
+    winner: usize,
+    identical: bool,
+}
+
+impl MixPackage {
+    /// Merges several packages into one, resolving duplicate entries by file ID
+    ///
+    /// Duplicates with the same contents never conflict. The merged package takes the game of the first
+    /// package and gets a local mix database that unites the names of every package.
+    ///
+    /// # Arguments
+    /// * `packages` - The packages to merge, in priority order
+    /// * `policy` - How to resolve entries whose contents differ
+    ///
+    /// # Returns
+    /// The merged package with a report of the duplicate entries, or an error if the policy rejects a conflict
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{ConflictPolicy, MixPackage};
+    ///
+    /// let mut first = MixPackage::default();
+    /// first.add_any("rules.ini".to_string(), b"first".to_vec());
+    /// let mut second = MixPackage::default();
+    /// second.add_any("rules.ini".to_string(), b"second".to_vec());
+    ///
+    /// let (merged, report) = MixPackage::merge(vec![first, second], ConflictPolicy::LastWins).unwrap();
+    /// assert_eq!(merged.files["rules.ini"], b"second");
+    /// assert_eq!(report.conflicts.len(), 1);
+    /// ```
+    pub fn merge(packages: Vec<MixPackage>, policy: ConflictPolicy) -> Result<(MixPackage, MergeReport), MixError> {
+        let game = packages.first().map(|package| package.game).unwrap_or_default();
+        let mut names: BTreeSet<String> = BTreeSet::new();
+        let mut entries: BTreeMap<i32, MergeEntry> = BTreeMap::new();
+
+        for (index, package) in packages.into_iter().enumerate() {
+            if let Some(database) = package.database {
+                names.extend(database.names);
+            }
+            let named = package.files.into_iter().map(|(name, data)| (ra2_crc(&name), Some(name), data));
+            let unnamed = package.unnamed.into_iter().map(|(id, data)| (id, None, data));
+            for (id, name, data) in named.chain(unnamed) {
+                let entry = match entries.entry(id) {
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(MergeEntry { name, data, sources: vec![index], winner: index, identical: true });
+                        continue;
+                    }
+                    Entry::Occupied(occupied) => occupied.into_mut(),
+                };
+                if entry.sources.contains(&index) {
+                    continue;
+                }
+                entry.sources.push(index);
+                if entry.name.is_none() {
+                    entry.name = name;
+                }
+                if entry.data == data {
+                    continue;
+                }
+                entry.identical = false;
+                match policy {
+                    ConflictPolicy::FirstWins => {}
+                    ConflictPolicy::LastWins => {
+                        entry.data = data;
+                        entry.winner = index;
+                    }
+                    ConflictPolicy::Error => {
+                        return Err(MixError::EntryConflict { id, name: entry.name.clone() });
+                    }
+                }
+            }
+        }
+
+        let mut merged = MixPackage { game, database_policy: DatabasePolicy::Merge, ..Default::default() };
+        let mut report = MergeReport::default();
+        for (id, entry) in entries {
+            if entry.sources.len() > 1 {
+                report.conflicts.push(MergeConflict {
+                    id,
+                    name: entry.name.clone(),
+                    sources: entry.sources,
+                    winner: entry.winner,
+                    identical: entry.identical,
+                });
+            }
+            match entry.name {
+                Some(name) => merged.files.insert(name, entry.data),
+                None => merged.unnamed.insert(id, entry.data),
+            };
+        }
+        merged.database = Some(LocalMixDatabase { game, names: names.into_iter().collect() });
+        Ok((merged, report))
+    }
+
+    /// Loads several MIX files and merges them with [`MixPackage::merge`]
+    ///
+    /// # Arguments
+    /// * `inputs` - The paths of the MIX files, in priority order
+    /// * `policy` - How to resolve entries whose contents differ
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use ra2_mix::{ConflictPolicy, MixPackage};
+    /// use std::path::PathBuf;
+    ///
+    /// let inputs = [PathBuf::from("expandmd01.mix"), PathBuf::from("expandmd02.mix")];
+    /// let (merged, _) = MixPackage::merge_files(&inputs, ConflictPolicy::FirstWins).unwrap();
+    /// merged.save(&PathBuf::from("expandmd99.mix")).unwrap();
+    /// ```
+    pub fn merge_files(inputs: &[PathBuf], policy: ConflictPolicy) -> Result<(MixPackage, MergeReport), MixError> {
+        let packages = inputs.iter().map(|input| MixPackage::load(input)).collect::<Result<Vec<_>, _>>()?;
+        MixPackage::merge(packages, policy)
+    }
+}
@@ -1,32 +1,63 @@
 use crate::{
-    MixError, XccGame,
+    MixError, MixWarning, XccGame,
     checksum::ra2_crc,
     constants::*,
     crypto::{decrypt_blowfish_key, decrypt_mix_header, get_decryption_block_sizing},
+    diagnostics::report,
+    parallel::{batch_size, par_for_each, par_map},
+    progress::{MixTask, Progress, ProgressStage},
+    xcc_package::{
+        database::{DatabasePolicy, LocalMixDatabase, detect_game},
+        index::MixIndex,
+        writer::BodyLayout,
+    },
 };
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs::File,
     io::{Seek, SeekFrom, Write},
-    path::Path,
+    path::{Component, Path, PathBuf},
 };
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod database;
+pub mod diff;
+pub mod index;
+pub mod merge;
 pub mod reader;
+pub mod stream;
 pub mod writer;
 
 /// MIX package
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MixPackage {
     /// The game version of the MIX package
     pub game: XccGame,
     /// A map of file names to file data
     pub files: HashMap<String, Vec<u8>>,
+    /// A map of file IDs to file data for entries whose name is unknown
+    ///
+    /// Entries in `files` take precedence over unnamed entries with the same ID.
+    pub unnamed: HashMap<i32, Vec<u8>>,
+    /// The local mix database read from the archive, if it had one
+    pub database: Option<LocalMixDatabase>,
+    /// How the local mix database is written when the package is encoded
+    pub database_policy: DatabasePolicy,
+    /// The header and index read from the archive, if the package was decoded
+    pub index: Option<MixIndex>,
+    /// How the entry bodies are placed when the package is encoded
+    pub layout: BodyLayout,
+    /// Whether a SHA-1 digest of the body is appended when the package is encoded
+    pub checksum: bool,
 }
 
 /// MIX file header
-#[derive(Copy, Debug, Clone)]
-struct MixHeader {
+#[derive(Copy, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct MixHeader {
     /// Flags (None for old format)
     pub flags: Option<u32>,
     /// Number of files in the MIX
@@ -36,8 +67,9 @@ struct MixHeader {
 }
 
 /// MIX file entry
-#[derive(Debug, Clone, Copy)]
-struct FileEntry {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_json", derive(serde::Serialize, serde::Deserialize))]
+pub struct MixEntry {
     /// File ID (CRC of filename)
     pub id: i32,
     /// Offset in the body data
@@ -47,16 +79,27 @@ struct FileEntry {
 }
 /// File information for MIX file creation
 #[derive(Debug, Clone)]
-struct FileInfo {
+pub(crate) struct FileInfo<'a> {
     /// File ID (CRC of filename)
     file_id: i32,
-    /// File data
-    data: Vec<u8>,
+    /// File name, if known
+    name: Option<String>,
+    /// File data, borrowed from the package except for the generated local mix database
+    data: Cow<'a, [u8]>,
 }
 
 impl Default for MixPackage {
     fn default() -> Self {
-        Self { game: XccGame::RA2, files: Default::default() }
+        Self {
+            game: XccGame::RA2,
+            files: Default::default(),
+            unnamed: Default::default(),
+            database: None,
+            database_policy: Default::default(),
+            index: None,
+            layout: Default::default(),
+            checksum: false,
+        }
     }
 }
 
@@ -96,14 +139,96 @@ impl MixPackage {
     /// ```
     pub fn add_file(&mut self, data: &Path) -> Result<usize, MixError> {
         if !data.is_file() {
-            return Err(MixError::FileNotFound("".to_string()));
+            return Err(MixError::FileNotFound(data.to_path_buf()));
         }
-        let name = data.file_name().and_then(|s| s.to_str()).ok_or(MixError::FileNotFound("".to_string()))?;
+        let name = data.file_name().and_then(|s| s.to_str()).ok_or(MixError::FileNotFound(data.to_path_buf()))?;
         let data = std::fs::read(data)?;
         let size = data.len();
         self.files.insert(name.to_string(), data);
         Ok(size)
     }
+
+    /// Adds a file, replacing any entry with the same file ID, such as one named in another case or unnamed
+    ///
+    /// # Arguments
+    /// * `name` - The file name
+    /// * `data` - The file data
+    ///
+    /// # Returns
+    /// The data of the replaced entry, if any
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::MixPackage;
+    ///
+    /// let mut mix = MixPackage::default();
+    /// mix.insert("RULES.INI".to_string(), b"[Old]".to_vec());
+    /// assert_eq!(mix.insert("rules.ini".to_string(), b"[New]".to_vec()), Some(b"[Old]".to_vec()));
+    /// assert_eq!(mix.files.len(), 1);
+    /// ```
+    pub fn insert(&mut self, name: String, data: Vec<u8>) -> Option<Vec<u8>> {
+        let replaced = self.remove_by_id(ra2_crc(&name));
+        self.files.insert(name, data);
+        replaced
+    }
+
+    /// The data of the entry with a file ID, named or unnamed
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{MixPackage, ra2_crc};
+    ///
+    /// let mut mix = MixPackage::default();
+    /// mix.add_any("rules.ini".to_string(), b"[General]".to_vec());
+    /// assert_eq!(mix.get_by_id(ra2_crc("RULES.INI")), Some(b"[General]".as_slice()));
+    /// ```
+    pub fn get_by_id(&self, id: i32) -> Option<&[u8]> {
+        self.entry_by_id(id).map(|(_, data)| data)
+    }
+
+    /// Removes the entry with a file ID, named or unnamed
+    ///
+    /// # Returns
+    /// The data of the removed entry, `None` if the package has no such entry
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::MixPackage;
+    ///
+    /// let mut mix = MixPackage::default();
+    /// mix.unnamed.insert(0x1234, b"data".to_vec());
+    /// assert_eq!(mix.remove_by_id(0x1234), Some(b"data".to_vec()));
+    /// assert_eq!(mix.remove_by_id(0x1234), None);
+    /// ```
+    pub fn remove_by_id(&mut self, id: i32) -> Option<Vec<u8>> {
+        let names: Vec<String> = self.files.keys().filter(|name| ra2_crc(name) == id).cloned().collect();
+        let mut removed = self.unnamed.remove(&id);
+        // Named data wins, as in `get_by_id`
+        for name in names {
+            removed = self.files.remove(&name).or(removed);
+        }
+        removed
+    }
+
+    /// Collects every entry by file ID, with its name if known
+    pub(crate) fn entries_by_id(&self) -> BTreeMap<i32, (Option<&str>, &[u8])> {
+        let mut entries = BTreeMap::new();
+        for (id, data) in &self.unnamed {
+            entries.insert(*id, (None, data.as_slice()));
+        }
+        for (name, data) in &self.files {
+            entries.insert(ra2_crc(name), (Some(name.as_str()), data.as_slice()));
+        }
+        entries
+    }
+
+    /// Finds the entry with a file ID, with its name if known
+    pub(crate) fn entry_by_id(&self, id: i32) -> Option<(Option<&str>, &[u8])> {
+        match self.files.iter().find(|(name, _)| ra2_crc(name) == id) {
+            Some((name, data)) => Some((Some(name.as_str()), data.as_slice())),
+            None => self.unnamed.get(&id).map(|data| (None, data.as_slice())),
+        }
+    }
 }
 
 /// Extract single file from the MIX file to a folder
@@ -120,36 +245,113 @@ impl MixPackage {
 /// ```
 /// ```
 pub fn extract(input: &Path, output: &Path) -> Result<(), MixError> {
-    let xcc = MixPackage::load(input)?;
-    let file_map = xcc.files;
+    extract_with(input, output, &mut MixTask::new())
+}
+
+/// The path an entry is extracted to inside a folder
+///
+/// Names come from the local mix database of the archive and cannot be trusted. A name that is not a plain file name,
+/// such as `..`, `C:rules.ini` or one holding a `/` or `\`, could point outside the folder, so the entry is written
+/// under its hexadecimal file ID instead.
+///
+/// # Arguments
+/// * `folder` - The folder entries are extracted to
+/// * `name` - The file name of the entry
+/// * `id` - The file ID of the entry
+///
+/// # Examples
+/// ```
+/// use ra2_mix::extract_path;
+/// use std::path::Path;
+///
+/// assert_eq!(extract_path(Path::new("out"), "rules.ini", 1), Path::new("out").join("rules.ini"));
+/// assert_eq!(extract_path(Path::new("out"), "../evil.dll", 0x1234), Path::new("out").join("00001234"));
+/// ```
+pub fn extract_path(folder: &Path, name: &str, id: i32) -> PathBuf {
+    let mut components = Path::new(name).components();
+    let plain = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        && !name.contains(['/', '\\', ':']);
+    if plain { folder.join(name) } else { folder.join(format!("{:08X}", id)) }
+}
+
+/// Extracts every named file of the MIX file to a folder, reporting progress and checking for cancellation
+///
+/// Files whose name could point outside the folder are written under their file ID, see [`extract_path`].
+///
+/// # Arguments
+/// * `input` - Path to the MIX file
+/// * `output` - The folder to write the files to
+/// * `task` - The progress observer and cancellation token
+pub fn extract_with(input: &Path, output: &Path, task: &mut MixTask) -> Result<(), MixError> {
+    let (xcc, _) = MixPackage::load_with(input, task)?;
+    let mut file_map: Vec<(String, Vec<u8>)> = xcc.files.into_iter().collect();
+    file_map.sort_by(|a, b| a.0.cmp(&b.0));
     std::fs::create_dir_all(output)?;
-    for (filename, file_data) in file_map {
-        let file_path = output.join(filename);
-        let mut file = File::create(file_path)?;
-        file.write_all(&file_data)?;
+    let mut progress = Progress {
+        stage: ProgressStage::Extract,
+        entry: 0,
+        entries: file_map.len(),
+        bytes: 0,
+        total_bytes: file_map.iter().map(|(_, data)| data.len() as u64).sum(),
+    };
+    // Files of a batch are written concurrently with the `parallel` feature
+    for batch in file_map.chunks(batch_size()) {
+        let results = par_map(batch, |(filename, file_data)| -> Result<(), MixError> {
+            let mut file = File::create(extract_path(output, filename, ra2_crc(filename)))?;
+            file.write_all(file_data)?;
+            Ok(())
+        });
+        for (result, (_, file_data)) in results.into_iter().zip(batch) {
+            result?;
+            progress.entry += 1;
+            progress.bytes += file_data.len() as u64;
+            task.step(progress)?;
+        }
     }
 
     Ok(())
 }
-/// Patch a folder into the MIX file
+/// Adds every file of a folder to the MIX file, creating it if needed
 ///
-/// # Arguments
+/// The entries already in `output` are kept, files of the folder replace the entries with the same name. Subfolders
+/// are not read.
 ///
-/// * `input`:
-/// * `output`:
-///
-/// returns: Result<(), MixError>
+/// # Arguments
+/// * `input` - The folder holding the files to add
+/// * `output` - Path to the MIX file to update
+pub fn patch(input: &Path, output: &Path) -> Result<(), MixError> {
+    patch_with(input, output, &mut MixTask::new())
+}
+
+/// Adds every file of a folder to the MIX file, creating it if needed, reporting progress and checking for cancellation
 ///
-/// # Examples
+/// See [`patch`] for how the folder and the existing entries are merged.
 ///
-/// ```
-/// ```
-pub fn patch(input: &Path, output: &Path) -> Result<(), MixError> {
-    let mut xcc = MixPackage::load(input)?;
+/// # Arguments
+/// * `input` - The folder holding the files to add
+/// * `output` - Path to the MIX file to update
+/// * `task` - The progress observer and cancellation token
+pub fn patch_with(input: &Path, output: &Path, task: &mut MixTask) -> Result<(), MixError> {
+    let mut xcc = if output.is_file() { MixPackage::load_with(output, task)?.0 } else { MixPackage::default() };
+    let mut paths = Vec::new();
     for entry in std::fs::read_dir(input)? {
-        let entry = entry?;
-        xcc.add_file(&entry.path())?;
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    let mut progress = Progress {
+        stage: ProgressStage::Collect,
+        entry: 0,
+        entries: paths.len(),
+        bytes: 0,
+        total_bytes: paths.iter().filter_map(|path| path.metadata().ok()).map(|metadata| metadata.len()).sum(),
+    };
+    for path in paths {
+        progress.bytes += xcc.add_file(&path)? as u64;
+        progress.entry += 1;
+        task.step(progress)?;
     }
-    xcc.save(output)?;
+    xcc.save_with(output, task)?;
     Ok(())
 }
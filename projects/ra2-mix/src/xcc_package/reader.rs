@@ -14,8 +14,31 @@ impl MixPackage {
     /// ```
     /// ```
     pub fn load(mix_path: &Path) -> Result<Self, MixError> {
-        let data = std::fs::read(mix_path)?;
-        MixPackage::decode(&data)
+        Ok(MixPackage::load_with_warnings(mix_path)?.0)
+    }
+    /// Reads a MIX file from disk, returning the warnings found along the way
+    ///
+    /// # Arguments
+    /// * `mix_path` - Path to the MIX file
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    pub fn load_with_warnings(mix_path: &Path) -> Result<(Self, Vec<MixWarning>), MixError> {
+        MixPackage::load_with(mix_path, &mut MixTask::new())
+    }
+    /// Reads a MIX file from disk, reporting progress and checking for cancellation after every entry
+    ///
+    /// # Arguments
+    /// * `mix_path` - Path to the MIX file
+    /// * `task` - The progress observer and cancellation token
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    pub fn load_with(mix_path: &Path, task: &mut MixTask) -> Result<(Self, Vec<MixWarning>), MixError> {
+        let context = |e: MixError| e.context(format!("Failed to load `{}`", mix_path.display()));
+        task.check()?;
+        let data = std::fs::read(mix_path).map_err(|e| context(e.into()))?;
+        MixPackage::decode_with(&data, task).map_err(context)
     }
     /// Reads a MIX file and returns a map of filenames to file data
     ///
@@ -30,62 +53,79 @@ impl MixPackage {
     /// ```
     /// ```
     pub fn decode(mix_data: &[u8]) -> Result<Self, MixError> {
-        let (header, file_entries, mix_data_vec) = read_file_info(mix_data)?;
-        let map = get_file_map(&file_entries, &mix_data_vec, &header)?;
-        Ok(Self { game: Default::default(), files: map })
+        Ok(MixPackage::decode_with_warnings(mix_data)?.0)
+    }
+    /// Reads a MIX file from memory, returning the warnings found along the way
+    ///
+    /// # Arguments
+    /// * `mix_data` - The bytes of the MIX file
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{DatabasePolicy, MixPackage, MixWarning};
+    ///
+    /// let mut mix = MixPackage { database_policy: DatabasePolicy::Omit, ..Default::default() };
+    /// mix.add_any("a.txt".to_string(), b"a".to_vec());
+    /// mix.add_any("b.txt".to_string(), b"b".to_vec());
+    /// let (_, warnings) = MixPackage::decode_with_warnings(&mix.encode().unwrap()).unwrap();
+    /// assert_eq!(warnings[0], MixWarning::MissingDatabase);
+    /// ```
+    pub fn decode_with_warnings(mix_data: &[u8]) -> Result<(Self, Vec<MixWarning>), MixError> {
+        MixPackage::decode_with(mix_data, &mut MixTask::new())
+    }
+    /// Reads a MIX file from memory, reporting progress and checking for cancellation after every entry
+    ///
+    /// # Arguments
+    /// * `mix_data` - The bytes of the MIX file
+    /// * `task` - The progress observer and cancellation token
+    ///
+    /// # Returns
+    /// The package and every [`MixWarning`] reported while reading it
+    pub fn decode_with(mix_data: &[u8], task: &mut MixTask) -> Result<(Self, Vec<MixWarning>), MixError> {
+        let mut warnings = Vec::new();
+        let index = MixIndex::parse(mix_data)?;
+        let package = get_file_map(&index, mix_data, &mut warnings, task)?;
+        Ok((package, warnings))
     }
 }
 
-/// Checks if a MIX header is encrypted
-fn header_is_encrypted(header: &MixHeader) -> bool {
-    header.flags.is_some() && (header.flags.unwrap() & 0x20000) != 0
+/// Extracts file data from MIX body, reading entries outside the body as empty
+fn get_file_data_from_mix_body(file_entry: &MixEntry, mix_body_data: &[u8], warnings: &mut Vec<MixWarning>) -> Vec<u8> {
+    get_checked_file_data(file_entry, mix_body_data).unwrap_or_else(|_| {
+        report(warnings, out_of_bounds(file_entry));
+        Vec::new()
+    })
 }
 
-/// Parses file entries from index data
-fn get_file_entries(file_count: usize, index_data: &[u8]) -> Result<Vec<FileEntry>, MixError> {
-    let mut file_entries = Vec::with_capacity(file_count);
-    let mut cursor = std::io::Cursor::new(index_data);
-
-    for _ in 0..file_count {
-        let id = cursor.read_i32::<LittleEndian>()?;
-        let offset = cursor.read_i32::<LittleEndian>()?;
-        let size = cursor.read_i32::<LittleEndian>()?;
-        file_entries.push(FileEntry { id, offset, size });
-    }
-
-    Ok(file_entries)
+/// The warning for an entry that lies outside the body
+pub(crate) fn out_of_bounds(entry: &MixEntry) -> MixWarning {
+    MixWarning::EntryOutOfBounds { id: entry.id, offset: entry.offset as i64, size: entry.size as i64 }
 }
 
-/// Extracts filenames from a MIX database file
-fn get_filenames_from_mix_db(mix_db_file_data: &[u8]) -> Vec<String> {
-    let mut filenames = Vec::new();
-    let mut start = XCC_HEADER_SIZE;
-
-    while start < mix_db_file_data.len() {
-        let mut end = start;
-        while end < mix_db_file_data.len() && mix_db_file_data[end] != 0 {
-            end += 1;
+/// Finds the local mix database of an archive whose body holds `body_size` bytes
+///
+/// Protected archives point the database outside the body, this is reported and handled as if it were missing.
+///
+/// # Returns
+/// The offset and size of the database in the body, if it has a readable one
+pub(crate) fn database_range(index: &MixIndex, body_size: u64, warnings: &mut Vec<MixWarning>) -> Option<(u64, u64)> {
+    let entry = index.entry(ra2_crc(MIX_DB_FILENAME))?;
+    match entry.range(body_size) {
+        Ok(range) => Some(range),
+        Err(_) => {
+            report(warnings, out_of_bounds(entry));
+            None
         }
-
-        if start < end {
-            if let Ok(filename) = std::str::from_utf8(&mix_db_file_data[start..end]) {
-                filenames.push(filename.to_string());
-            }
-        }
-
-        end += 1; // Skip the null terminator
-        start = end;
     }
-
-    filenames
 }
 
-/// Extracts file data from MIX body
-fn get_file_data_from_mix_body(file_entry: &FileEntry, mix_body_data: &[u8]) -> Vec<u8> {
-    let start = file_entry.offset as usize;
-    let end = start + file_entry.size as usize;
-
-    if end <= mix_body_data.len() { mix_body_data[start..end].to_vec() } else { Vec::new() }
+/// Extracts file data from MIX body, failing if the entry lies outside the body
+fn get_checked_file_data(file_entry: &MixEntry, mix_body_data: &[u8]) -> Result<Vec<u8>, MixError> {
+    let (start, size) = file_entry.range(mix_body_data.len() as u64)?;
+    Ok(mix_body_data[start as usize..(start + size) as usize].to_vec())
 }
 
 /// Loads the global mix database
@@ -96,139 +136,109 @@ pub fn load_global_mix_database() -> Result<HashMap<String, i32>, MixError> {
     Ok(HashMap::new())
 }
 
-/// Reads file information from a MIX file
-fn read_file_info(mix_data: &[u8]) -> Result<(MixHeader, Vec<FileEntry>, Vec<u8>), MixError> {
-    let mut cursor = std::io::Cursor::new(mix_data);
-
-    // Check if this is an old format MIX file
-    let first_word = cursor.read_u16::<LittleEndian>()?;
-    cursor.seek(SeekFrom::Start(0))?; // Reset cursor position
-
-    let header: MixHeader;
-    let header_size: usize;
-
-    if first_word != 0 {
-        // Old format
-        let count = cursor.read_u16::<LittleEndian>()?;
-        let size = cursor.read_u32::<LittleEndian>()?;
-        header = MixHeader { flags: None, file_count: count, data_size: size };
-        header_size = MIN_HEADER_SIZE;
-    }
-    else {
-        // New format
-        let flags = cursor.read_u32::<LittleEndian>()?;
-        let count = cursor.read_u16::<LittleEndian>()?;
-        let size = cursor.read_u32::<LittleEndian>()?;
-        header = MixHeader { flags: Some(flags), file_count: count, data_size: size };
-        header_size = HEADER_SIZE;
-    }
-
-    let file_entries: Vec<FileEntry>;
-    let mut updated_header = header.clone();
-
-    if header_is_encrypted(&header) {
-        // Handle encrypted header
-        let encrypted_key_start = SIZE_OF_FLAGS;
-        let encrypted_key_end = encrypted_key_start + SIZE_OF_ENCRYPTED_KEY;
-
-        let encrypted_blowfish_key = &mix_data[encrypted_key_start..encrypted_key_end];
-        let decrypted_blowfish_key = decrypt_blowfish_key(encrypted_blowfish_key)?;
-
-        let (file_count, data_size, index_data) = decrypt_mix_header(mix_data, &decrypted_blowfish_key)?;
-
-        file_entries = get_file_entries(file_count as usize, &index_data)?;
-        updated_header.file_count = file_count;
-        updated_header.data_size = data_size;
-    }
-    else {
-        // Handle unencrypted header
-        let index_start = header_size;
-        let index_end = index_start + (header.file_count as usize * FILE_ENTRY_SIZE);
+/// The game, local mix database and entry names resolved for an archive
+#[derive(Debug)]
+pub(crate) struct ResolvedNames {
+    pub game: XccGame,
+    pub database: Option<LocalMixDatabase>,
+    pub names: HashMap<i32, String>,
+}
 
-        if index_end > mix_data.len() {
-            return Err(MixError::InvalidFormat("File too small for index".to_string()));
+/// Resolves the game and entry names of an archive from the bytes of its local mix database, if it has one
+///
+/// A malformed database is reported and ignored, the game is then guessed from the entries as if it were missing.
+pub(crate) fn resolve_names(
+    entries: &[MixEntry],
+    database: Option<&[u8]>,
+    warnings: &mut Vec<MixWarning>,
+) -> Result<ResolvedNames, MixError> {
+    let database = match database.map(LocalMixDatabase::decode) {
+        Some(Ok(database)) => database,
+        Some(Err(_)) => {
+            report(warnings, MixWarning::InvalidDatabase);
+            return resolve_without_database(entries);
         }
-
-        let index_data = &mix_data[index_start..index_end];
-        file_entries = get_file_entries(header.file_count as usize, index_data)?;
-    }
-
-    // Convert mix_data_ref to owned Vec<u8>
-    let mix_data_vec = mix_data.to_vec();
-
-    Ok((updated_header, file_entries, mix_data_vec))
+        None => {
+            report(warnings, MixWarning::MissingDatabase);
+            return resolve_without_database(entries);
+        }
+    };
+    // Use local mix database
+    let names = database.names.iter().map(|filename| (ra2_crc(filename), filename.clone())).collect();
+    Ok(ResolvedNames { game: database.game, database: Some(database), names })
 }
 
-/// Creates a file map from file entries and mix data
-fn get_file_map(file_entries: &[FileEntry], mix_data: &[u8], header: &MixHeader) -> Result<HashMap<String, Vec<u8>>, MixError> {
-    if file_entries.len() <= 1 {
-        return Ok(HashMap::new());
-    }
+/// Guesses the game from the entries and takes the names from the global mix database
+fn resolve_without_database(entries: &[MixEntry]) -> Result<ResolvedNames, MixError> {
+    let ids: Vec<i32> = entries.iter().map(|entry| entry.id).collect();
+    let game = detect_game(&ids).unwrap_or_default();
+    // Use global mix database
+    #[cfg(feature = "serde_json")]
+    let names = load_global_mix_database()?.into_iter().map(|(filename, id)| (id, filename)).collect();
+    // Without serde support, we can't load the global database
+    #[cfg(not(feature = "serde_json"))]
+    let names = HashMap::new();
+    Ok(ResolvedNames { game, database: None, names })
+}
 
+/// Creates the package from the index and mix data, resolving names and the game from the local mix database
+fn get_file_map(
+    index: &MixIndex,
+    mix_data: &[u8],
+    warnings: &mut Vec<MixWarning>,
+    task: &mut MixTask,
+) -> Result<MixPackage, MixError> {
+    let file_entries = &index.entries;
     let mix_db_id = ra2_crc(MIX_DB_FILENAME);
     debug_assert_eq!(mix_db_id, 0x366E051F);
 
-    // Calculate body start position
-    let mut body_start =
-        if header.flags.is_none() { MIN_HEADER_SIZE } else { HEADER_SIZE } + (FILE_ENTRY_SIZE * file_entries.len());
-
-    if header_is_encrypted(header) {
-        body_start += SIZE_OF_ENCRYPTED_KEY;
-        body_start += get_decryption_block_sizing(header.file_count).1;
-    }
-
-    // Find local mix database if it exists
-    let mut local_mix_db_file_entry = None;
-    for entry in file_entries {
-        if entry.id == mix_db_id {
-            local_mix_db_file_entry = Some(*entry);
-            break;
-        }
-    }
-
+    let body_start = index.body_offset as usize;
+    MixError::check_bounds(body_start, 0, mix_data.len())?;
     let mix_body_data = &mix_data[body_start..];
 
-
-    // Get filename to ID mapping
-    let id_filename_map: HashMap<i32, String>;
-
-    if let Some(db_entry) = local_mix_db_file_entry {
-        if db_entry.offset < 0 { 
-            return Err(MixError::InvalidFormat("This `mix` file is protected".to_string()));
-        }
-        // Use local mix database
-        let local_mix_db_data = get_file_data_from_mix_body(&db_entry, mix_body_data);
-        let filenames = get_filenames_from_mix_db(&local_mix_db_data);
-
-        id_filename_map = filenames.iter().map(|filename| (ra2_crc(filename), filename.clone())).collect();
-    }
-    else {
-        println!("No local mix database found, please add global mix database");
-        // Use global mix database
-        #[cfg(feature = "serde_json")]
-        {
-            let global_db = load_global_mix_database()?;
-            id_filename_map = global_db.iter().map(|(filename, id)| (*id, filename.clone())).collect();
-        }
-
-        #[cfg(not(feature = "serde_json"))]
-        {
-            // Without serde support, we can't load the global database
-            // Return an empty map
-            id_filename_map = HashMap::new();
-        }
-    }
+    let local_mix_db_data = database_range(index, mix_body_data.len() as u64, warnings)
+        .map(|(start, size)| &mix_body_data[start as usize..(start + size) as usize]);
+    let ResolvedNames { game, database, names: id_filename_map } =
+        resolve_names(file_entries, local_mix_db_data, warnings)?;
 
     // Create file map
     let mut filemap = HashMap::new();
+    let mut unnamed = HashMap::new();
+    let mut progress = Progress {
+        stage: ProgressStage::Decode,
+        entry: 0,
+        entries: file_entries.len(),
+        bytes: 0,
+        total_bytes: file_entries.iter().map(|entry| entry.size.max(0) as u64).sum(),
+    };
 
     for entry in file_entries {
-        let file_data = get_file_data_from_mix_body(entry, mix_body_data);
-
-        if let Some(filename) = id_filename_map.get(&entry.id) {
-            filemap.insert(filename.clone(), file_data);
+        // The database is kept apart from the regular files
+        if entry.id != mix_db_id {
+            let file_data = get_file_data_from_mix_body(entry, mix_body_data, warnings);
+
+            match id_filename_map.get(&entry.id) {
+                Some(filename) => filemap.insert(filename.clone(), file_data),
+                None => unnamed.insert(entry.id, file_data),
+            };
         }
+        progress.entry += 1;
+        progress.bytes += entry.size.max(0) as u64;
+        task.step(progress)?;
+    }
+
+    if database.is_some() && !unnamed.is_empty() {
+        report(warnings, MixWarning::UnnamedEntries { count: unnamed.len() });
     }
 
-    Ok(filemap)
+    Ok(MixPackage {
+        game,
+        files: filemap,
+        unnamed,
+        database,
+        database_policy: Default::default(),
+        index: Some(index.clone()),
+        layout: Default::default(),
+        checksum: index.header.has_checksum(),
+    })
 }
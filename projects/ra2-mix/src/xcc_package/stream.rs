@@ -0,0 +1,214 @@
+//! Streaming access to the entries of RA2 MIX files
+//!
+//! [`MixReader`] only loads the header and index up front, entries are read through bounded [`EntryReader`] views
+//! over the underlying source, so format decoders can be chained on top without copying payloads.
+
+use super::{
+    index::MixIndex,
+    reader::{ResolvedNames, database_range, resolve_names},
+    *,
+};
+use std::io::{BufReader, Read};
+
+/// Reads a MIX file entry by entry from a seekable source
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{MixPackage, MixReader};
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let mut mix = MixPackage::default();
+/// mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+/// let data = mix.encode().unwrap();
+///
+/// let mut reader = MixReader::new(std::io::Cursor::new(data)).unwrap();
+/// let mut entry = reader.entry_by_name("hello.txt").unwrap();
+/// entry.seek(SeekFrom::End(-6)).unwrap();
+/// let mut text = String::new();
+/// entry.read_to_string(&mut text).unwrap();
+/// assert_eq!(text, "World!");
+/// ```
+#[derive(Debug)]
+pub struct MixReader<R> {
+    reader: R,
+    index: MixIndex,
+    positions: HashMap<i32, usize>,
+    body_size: u64,
+    resolved: ResolvedNames,
+    warnings: Vec<MixWarning>,
+}
+
+/// A bounded view of one entry, seeking relative to the start of the entry
+#[derive(Debug)]
+pub struct EntryReader<'a, R> {
+    reader: &'a mut R,
+    start: u64,
+    size: u64,
+    position: u64,
+}
+
+impl MixReader<BufReader<File>> {
+    /// Opens a MIX file from disk
+    ///
+    /// # Arguments
+    /// * `mix_path` - Path to the MIX file
+    pub fn open(mix_path: &Path) -> Result<Self, MixError> {
+        let context = |e: MixError| e.context(format!("Failed to load `{}`", mix_path.display()));
+        let file = File::open(mix_path).map_err(|e| context(e.into()))?;
+        MixReader::new(BufReader::new(file)).map_err(context)
+    }
+}
+
+impl<R: Read + Seek> MixReader<R> {
+    /// Reads the header, index and local mix database of a MIX file
+    ///
+    /// # Arguments
+    /// * `reader` - The source of the MIX file, positioned anywhere
+    pub fn new(mut reader: R) -> Result<Self, MixError> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let index = MixIndex::read(&mut reader)?;
+        MixError::check_bounds(index.body_offset as usize, 0, length as usize)?;
+        let body_size = length - index.body_offset;
+
+        let mut warnings = Vec::new();
+        let database = match database_range(&index, body_size, &mut warnings) {
+            Some((start, size)) => {
+                reader.seek(SeekFrom::Start(index.body_offset + start))?;
+                let mut data = Vec::with_capacity(size as usize);
+                reader.by_ref().take(size).read_to_end(&mut data)?;
+                Some(data)
+            }
+            None => None,
+        };
+        let resolved = resolve_names(&index.entries, database.as_deref(), &mut warnings)?;
+        let positions = entry_positions(&index);
+        Ok(Self { reader, index, positions, body_size, resolved, warnings })
+    }
+    /// The header and index of the archive
+    pub fn index(&self) -> &MixIndex {
+        &self.index
+    }
+    /// The game of the archive, read from the local mix database or guessed from the entries
+    pub fn game(&self) -> XccGame {
+        self.resolved.game
+    }
+    /// The local mix database of the archive, if it has one
+    pub fn database(&self) -> Option<&LocalMixDatabase> {
+        self.resolved.database.as_ref()
+    }
+    /// The warnings reported while opening the archive
+    pub fn warnings(&self) -> &[MixWarning] {
+        &self.warnings
+    }
+    /// The file name of an entry, if the archive knows it
+    pub fn name(&self, id: i32) -> Option<&str> {
+        self.resolved.names.get(&id).map(|name| name.as_str())
+    }
+    /// Opens a view of an entry
+    ///
+    /// # Arguments
+    /// * `id` - The file ID of the entry
+    ///
+    /// # Returns
+    /// The view, or [`MixError::InvalidIndex`] if the entry lies outside the body
+    pub fn entry(&mut self, id: i32) -> Result<EntryReader<'_, R>, MixError> {
+        let entry = *self.find(id)?;
+        self.entry_at(&entry)
+    }
+    /// Opens a view of an index entry from its own range, even if another entry has the same file ID
+    pub(crate) fn entry_at(&mut self, entry: &MixEntry) -> Result<EntryReader<'_, R>, MixError> {
+        let (start, size) = entry.range(self.body_size)?;
+        let start = self.index.body_offset + start;
+        self.reader.seek(SeekFrom::Start(start))?;
+        Ok(EntryReader { reader: &mut self.reader, start, size, position: 0 })
+    }
+    /// Opens a view of an entry by file name
+    ///
+    /// # Arguments
+    /// * `name` - The file name of the entry, in any case
+    pub fn entry_by_name(&mut self, name: &str) -> Result<EntryReader<'_, R>, MixError> {
+        self.entry(ra2_crc(name))
+    }
+    /// Reads the whole data of an entry
+    ///
+    /// # Arguments
+    /// * `id` - The file ID of the entry
+    pub fn read_entry(&mut self, id: i32) -> Result<Vec<u8>, MixError> {
+        let mut entry = self.entry(id)?;
+        let mut data = Vec::with_capacity(entry.size as usize);
+        entry.read_to_end(&mut data)?;
+        Ok(data)
+    }
+    /// Gives back the underlying source
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+    /// Finds the index entry with the given file ID, failing if the archive has none
+    pub(crate) fn find(&self, id: i32) -> Result<&MixEntry, MixError> {
+        find_entry(&self.index, &self.positions, id)
+    }
+}
+
+impl<R> EntryReader<'_, R> {
+    /// The size of the entry in bytes
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+    /// Checks if the entry is empty
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl<R: Read + Seek> Read for EntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let limit = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let read = self.reader.read(&mut buf[..limit])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for EntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let position = match position {
+            Some(position) => position,
+            None => {
+                let message = "invalid seek to a negative or overflowing position";
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+            }
+        };
+        // Positions past the end are allowed, reads there return nothing
+        self.reader.seek(SeekFrom::Start(self.start.saturating_add(position)))?;
+        self.position = position;
+        Ok(position)
+    }
+}
+
+/// Maps every file ID to the position of its first entry in the index, so readers find entries in constant time
+pub(crate) fn entry_positions(index: &MixIndex) -> HashMap<i32, usize> {
+    let mut positions = HashMap::with_capacity(index.entries.len());
+    for (position, entry) in index.entries.iter().enumerate() {
+        positions.entry(entry.id).or_insert(position);
+    }
+    positions
+}
+
+/// Finds the entry with the given file ID through the positions of [`entry_positions`], failing if the archive has none
+pub(crate) fn find_entry<'a>(
+    index: &'a MixIndex,
+    positions: &HashMap<i32, usize>,
+    id: i32,
+) -> Result<&'a MixEntry, MixError> {
+    match positions.get(&id) {
+        Some(&position) => Ok(&index.entries[position]),
+        None => Err(MixError::EntryNotFound(id)),
+    }
+}
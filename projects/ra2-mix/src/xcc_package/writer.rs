@@ -1,6 +1,7 @@
 //! Writer module for RA2 MIX files
 
 use super::*;
+use sha1::{Digest, Sha1};
 
 impl MixPackage {
     /// # Arguments
@@ -12,7 +13,20 @@ impl MixPackage {
     /// ```
     /// ```
     pub fn save(self, output: &Path) -> Result<usize, MixError> {
-        let data = self.encode()?;
+        self.save_with(output, &mut MixTask::new())
+    }
+    /// Writes the package to disk, reporting progress and checking for cancellation after every entry
+    ///
+    /// Nothing is written if the operation is cancelled.
+    ///
+    /// # Arguments
+    /// * `output` - Path of the MIX file to write
+    /// * `task` - The progress observer and cancellation token
+    ///
+    /// # Returns
+    /// The size of the written file in bytes
+    pub fn save_with(self, output: &Path, task: &mut MixTask) -> Result<usize, MixError> {
+        let data = self.encode_with(task)?;
         std::fs::write(output, &data)?;
         Ok(data.len())
     }
@@ -25,89 +39,438 @@ impl MixPackage {
     /// ```
     /// ```
     pub fn encode(self) -> Result<Vec<u8>, MixError> {
-        let file_map = coalesce_input_files(self.game, &self.files)?;
+        self.encode_with(&mut MixTask::new())
+    }
+    /// Encodes the package, reporting progress and checking for cancellation after every entry
+    ///
+    /// Progress is reported as the bodies are copied into the output, which is most of the work.
+    ///
+    /// The output is reproducible: it only depends on the files, unnamed entries, game, database policy and layout
+    /// of the package, never on insertion order or hash seeds. Database names are sorted, bodies are laid out by the
+    /// [`BodyLayout`] and gaps are filled with zeros, so encoding the same package twice yields identical bytes.
+    ///
+    /// # Arguments
+    /// * `task` - The progress observer and cancellation token
+    ///
+    /// # Returns
+    /// The bytes of the MIX file
+    pub fn encode_with(self, task: &mut MixTask) -> Result<Vec<u8>, MixError> {
+        let encoded = self.encode_index(task)?;
+        let digest = encoded.digest();
+        let EncodedMix { head: mut mix_data, bodies, tail, .. } = encoded;
+        let body_start = mix_data.len();
+        let body_size = bodies.iter().map(|(gap, data)| gap + data.len()).sum::<usize>() + tail;
+        mix_data.resize(body_start + body_size, 0);
+        // Every body gets its own slot of the output, the gaps between them stay zero
+        let mut rest = &mut mix_data[body_start..];
+        let mut slots = Vec::with_capacity(bodies.len());
+        for (gap, data) in &bodies {
+            let (slot, after) = std::mem::take(&mut rest)[*gap..].split_at_mut(data.len());
+            slots.push((slot, data));
+            rest = after;
+        }
+        let mut progress = Progress {
+            stage: ProgressStage::Encode,
+            entry: 0,
+            entries: bodies.len(),
+            bytes: 0,
+            total_bytes: bodies.iter().map(|(_, data)| data.len() as u64).sum(),
+        };
+        // Bodies of a batch are copied concurrently with the `parallel` feature
+        for batch in slots.chunks_mut(batch_size()) {
+            par_for_each(batch, |(slot, data)| slot.copy_from_slice(data));
+            for (_, data) in batch.iter() {
+                progress.entry += 1;
+                progress.bytes += data.len() as u64;
+                task.step(progress)?;
+            }
+        }
+        if let Some(digest) = digest {
+            mix_data.extend_from_slice(&digest);
+        }
+        Ok(mix_data)
+    }
 
+    /// Encodes the header and index, returning them with the bodies in layout order
+    pub(crate) fn encode_index(&self, task: &mut MixTask) -> Result<EncodedMix<'_>, MixError> {
+        task.check()?;
         // Create file information list
-        let mut file_information_list: Vec<FileInfo> =
-            file_map.iter().map(|(filename, data)| FileInfo { file_id: ra2_crc(filename), data: data.clone() }).collect();
+        let mut file_information_list = coalesce_input_files(self)?;
+        check_limits(&file_information_list, VolumeLimits::default())?;
 
-        // Sort by file ID
+        // The index is always sorted by file ID, the game looks entries up by binary search
         file_information_list.sort_by_key(|file_info| file_info.file_id);
+        let order = self.layout.body_order(&file_information_list, self.index.as_ref());
 
-        // Generate file entries and body data
-        let mut offset = 0u32;
-        let mut file_entry_data = Vec::new();
-        let mut body_data = Vec::new();
+        // Place the bodies
+        let alignment = u64::from(self.layout.alignment.max(1));
+        let padding = u64::from(self.layout.padding);
+        let mut offsets = vec![0u64; file_information_list.len()];
+        let mut gaps = Vec::with_capacity(order.len());
+        let mut end = 0u64;
+        let mut position = 0u64;
+        for &i in &order {
+            let offset = position.next_multiple_of(alignment);
+            // The gap covers the padding of the previous body as well as the alignment
+            gaps.push((offset - end) as usize);
+            offsets[i] = offset;
+            end = offset + file_information_list[i].data.len() as u64;
+            position = end + padding;
+        }
+        if position > MAX_BODY_SIZE {
+            return Err(MixError::BodyTooLarge { size: position, limit: MAX_BODY_SIZE });
+        }
 
-        for file_info in &file_information_list {
+        // Generate file entries
+        let mut file_entry_data = Vec::with_capacity(file_information_list.len() * FILE_ENTRY_SIZE);
+        for (file_info, offset) in file_information_list.iter().zip(&offsets) {
             let size = file_info.data.len() as u32;
 
             // Write file entry
             file_entry_data.write_i32::<LittleEndian>(file_info.file_id)?;
-            file_entry_data.write_u32::<LittleEndian>(offset)?;
+            file_entry_data.write_u32::<LittleEndian>(*offset as u32)?;
             file_entry_data.write_u32::<LittleEndian>(size)?;
+        }
+
+        // Combine header and index
+        let flags = if self.checksum { FLAG_CHECKSUM } else { 0 };
+        let mut head = create_mix_header(flags, file_information_list.len() as u16, position as u32)?;
+        head.extend_from_slice(&file_entry_data);
+
+        let mut files: Vec<Option<FileInfo<'_>>> = file_information_list.into_iter().map(Some).collect();
+        let bodies = order.iter().zip(gaps).filter_map(|(&i, gap)| files[i].take().map(|file_info| (gap, file_info.data)));
+        let bodies = bodies.collect();
+        let tail = (position - end) as usize;
+        Ok(EncodedMix { head, bodies, tail, checksum: self.checksum })
+    }
+
+    /// Checks that the package, with its local mix database, fits in a single archive within the limits
+    ///
+    /// [`MixPackage::encode`] checks the limits of the format, [`VolumeLimits::default`].
+    ///
+    /// # Arguments
+    /// * `limits` - The maximum file count and body size
+    ///
+    /// # Returns
+    /// [`MixError::TooManyFiles`] or [`MixError::BodyTooLarge`] if the package exceeds the limits
+    pub fn check_limits(&self, limits: VolumeLimits) -> Result<(), MixError> {
+        check_limits(&coalesce_input_files(self)?, limits)
+    }
+
+    /// Spreads the files of the package across as many packages as needed to stay within the limits
+    ///
+    /// Named files are distributed in name order followed by unnamed files in ID order, and every
+    /// volume gets its own local mix database unless the package policy omits it.
+    ///
+    /// # Arguments
+    /// * `limits` - The maximum file count and body size of each volume
+    ///
+    /// # Returns
+    /// The volumes, or an error if a single file is larger than the body size limit
+    ///
+    /// # Examples
+    /// ```
+    /// use ra2_mix::{MixPackage, VolumeLimits};
+    ///
+    /// let mut mix = MixPackage::default();
+    /// for i in 0..10 {
+    ///     mix.add_any(format!("file{}.txt", i), vec![0; 100]);
+    /// }
+    /// let volumes = mix.split(VolumeLimits { max_files: 4, ..Default::default() }).unwrap();
+    /// assert_eq!(volumes.len(), 4);
+    /// ```
+    pub fn split(self, limits: VolumeLimits) -> Result<Vec<MixPackage>, MixError> {
+        let with_database = self.database_policy != DatabasePolicy::Omit;
+        let reserved_files = usize::from(with_database);
+        let max_files = limits.max_files.min(MAX_FILE_COUNT).saturating_sub(reserved_files).max(1);
+        let max_size = limits.max_size.min(MAX_BODY_SIZE);
+        // The database holds its header, every name and its own name
+        let database_size = |names: u64| {
+            if with_database { names + (XCC_HEADER_SIZE + MIX_DB_FILENAME.len() + 1) as u64 } else { 0 }
+        };
+
+        let mut files: Vec<(String, Vec<u8>)> = self.files.into_iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut unnamed: Vec<(i32, Vec<u8>)> = self.unnamed.into_iter().collect();
+        unnamed.sort_by_key(|(id, _)| *id);
+        let entries = files.into_iter().map(|(name, data)| (Ok(name), data));
+        let entries = entries.chain(unnamed.into_iter().map(|(id, data)| (Err(id), data)));
 
-            // Write file data
-            body_data.extend_from_slice(&file_info.data);
+        let new_volume = || MixPackage {
+            game: self.game,
+            files: HashMap::new(),
+            unnamed: HashMap::new(),
+            database: None,
+            database_policy: self.database_policy,
+            index: None,
+            layout: self.layout.clone(),
+            checksum: self.checksum,
+        };
+        let mut volumes = Vec::new();
+        let mut current = new_volume();
+        let mut data_size = 0u64;
+        let mut names_size = 0u64;
+        for (name, data) in entries {
+            let size = data.len() as u64;
+            let name_size = name.as_ref().map_or(0, |name| name.len() as u64 + 1);
+            if size + database_size(name_size) > max_size {
+                return Err(MixError::BodyTooLarge { size, limit: max_size });
+            }
+            let count = current.files.len() + current.unnamed.len();
+            let full = count >= max_files || data_size + size + database_size(names_size + name_size) > max_size;
+            if full && count != 0 {
+                volumes.push(std::mem::replace(&mut current, new_volume()));
+                data_size = 0;
+                names_size = 0;
+            }
+            data_size += size;
+            names_size += name_size;
+            match name {
+                Ok(name) => current.files.insert(name, data),
+                Err(id) => current.unnamed.insert(id, data),
+            };
+        }
+        if current.files.len() + current.unnamed.len() != 0 || volumes.is_empty() {
+            volumes.push(current);
+        }
+        Ok(volumes)
+    }
 
-            offset += size;
+    /// Splits the package with [`MixPackage::split`] and saves every volume as `{prefix}{NN}.mix`
+    ///
+    /// # Arguments
+    /// * `directory` - The folder to write the volumes to
+    /// * `prefix` - The volume name prefix, such as `expandmd`
+    /// * `first` - The number of the first volume
+    /// * `limits` - The maximum file count and body size of each volume
+    ///
+    /// # Returns
+    /// The paths of the written volumes
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use ra2_mix::{MixPackage, VolumeLimits};
+    /// use std::path::Path;
+    ///
+    /// let mix = MixPackage::load(Path::new("huge.mix")).unwrap();
+    /// mix.save_volumes(Path::new("out"), "expandmd", 10, VolumeLimits::default()).unwrap();
+    /// ```
+    pub fn save_volumes(
+        self,
+        directory: &Path,
+        prefix: &str,
+        first: u32,
+        limits: VolumeLimits,
+    ) -> Result<Vec<PathBuf>, MixError> {
+        std::fs::create_dir_all(directory)?;
+        let mut paths = Vec::new();
+        for (index, volume) in self.split(limits)?.into_iter().enumerate() {
+            let path = directory.join(format!("{}{:02}.mix", prefix, first as usize + index));
+            volume.save(&path)?;
+            paths.push(path);
         }
+        Ok(paths)
+    }
+}
 
-        // Combine all parts
-        let mut mix_data = create_mix_header(&file_map)?;
-        mix_data.extend_from_slice(&file_entry_data);
-        mix_data.extend_from_slice(&body_data);
+/// Limits of a single volume when a package is split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeLimits {
+    /// The maximum number of files per volume, including the local mix database
+    pub max_files: usize,
+    /// The maximum body size per volume in bytes
+    pub max_size: u64,
+}
 
-        Ok(mix_data)
+impl Default for VolumeLimits {
+    fn default() -> Self {
+        Self { max_files: MAX_FILE_COUNT, max_size: MAX_BODY_SIZE }
     }
 }
 
-/// Creates MIX database data
-fn get_mix_db_data(filenames: &[String], game: XccGame) -> Vec<u8> {
-    let num_files = filenames.len();
-    let db_size_in_bytes = XCC_HEADER_SIZE + filenames.iter().map(|filename| filename.len() + 1).sum::<usize>();
+/// How entry bodies are placed when a package is encoded
+///
+/// The index is always sorted by file ID, only the bodies move.
+///
+/// # Examples
+/// ```
+/// use ra2_mix::{BodyLayout, EntryOrder, MixIndex, MixPackage, ra2_crc};
+///
+/// let mut mix = MixPackage::default();
+/// mix.add_any("b.txt".to_string(), b"bb".to_vec());
+/// mix.add_any("a.txt".to_string(), b"aaa".to_vec());
+/// mix.layout = BodyLayout { alignment: 16, order: EntryOrder::Name, padding: 0 };
+/// let index = MixIndex::parse(&mix.encode().unwrap()).unwrap();
+/// assert_eq!(index.entry(ra2_crc("a.txt")).unwrap().offset, 0);
+/// assert_eq!(index.entry(ra2_crc("b.txt")).unwrap().offset, 16);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyLayout {
+    /// The boundary every body starts on, `0` and `1` pack bodies back to back
+    pub alignment: u32,
+    /// The order of the bodies
+    pub order: EntryOrder,
+    /// The number of zero bytes reserved after every body, so entries can later grow in place
+    pub padding: u32,
+}
 
-    let mut bytes_data = Vec::with_capacity(db_size_in_bytes);
+impl Default for BodyLayout {
+    fn default() -> Self {
+        Self { alignment: 1, order: EntryOrder::default(), padding: 0 }
+    }
+}
 
-    // Write XCC ID bytes
-    bytes_data.extend_from_slice(XCC_ID_BYTES);
-    // Pad to 32 bytes
-    bytes_data.resize(32, 0);
+/// Order of the entry bodies in an encoded archive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EntryOrder {
+    /// By file ID, the same order as the index
+    #[default]
+    Id,
+    /// By file name, followed by unnamed entries by file ID
+    Name,
+    /// By offset in the archive the package was read from, followed by new entries by file ID
+    OriginalOffset,
+    /// The listed file names first, in list order, followed by the other entries by file ID
+    Priority(Vec<String>),
+}
 
-    // Write header fields
-    bytes_data.write_u32::<LittleEndian>(db_size_in_bytes as u32).unwrap();
-    bytes_data.write_u32::<LittleEndian>(XCC_FILE_TYPE).unwrap();
-    bytes_data.write_u32::<LittleEndian>(XCC_FILE_VERSION).unwrap();
-    bytes_data.write_u32::<LittleEndian>(game as u32).unwrap();
-    bytes_data.write_u32::<LittleEndian>(num_files as u32).unwrap();
+impl BodyLayout {
+    /// The positions of the files, sorted by file ID, in body order
+    fn body_order(&self, file_information_list: &[FileInfo], original: Option<&MixIndex>) -> Vec<usize> {
+        // Sorts are stable, so ties stay in file ID order
+        let mut order: Vec<usize> = (0..file_information_list.len()).collect();
+        match &self.order {
+            EntryOrder::Id => {}
+            EntryOrder::Name => order.sort_by(|&a, &b| {
+                let (a, b) = (&file_information_list[a].name, &file_information_list[b].name);
+                (a.is_none(), a).cmp(&(b.is_none(), b))
+            }),
+            EntryOrder::OriginalOffset => {
+                let offsets: HashMap<i32, i32> = match original {
+                    Some(index) => index.entries.iter().map(|entry| (entry.id, entry.offset)).collect(),
+                    None => HashMap::new(),
+                };
+                let original_offset = |i: usize| offsets.get(&file_information_list[i].file_id).copied();
+                order.sort_by_key(|&i| original_offset(i).map_or((true, 0), |offset| (false, offset)));
+            }
+            EntryOrder::Priority(names) => {
+                let mut ranks = HashMap::with_capacity(names.len());
+                for (rank, name) in names.iter().enumerate() {
+                    ranks.entry(ra2_crc(name)).or_insert(rank);
+                }
+                order.sort_by_key(|&i| ranks.get(&file_information_list[i].file_id).copied().unwrap_or(usize::MAX));
+            }
+        }
+        order
+    }
+}
+
+/// An encoded archive, with the bodies still apart from the header and index
+pub(crate) struct EncodedMix<'a> {
+    /// The header and index
+    pub head: Vec<u8>,
+    /// The number of zero bytes before every body, and the body borrowed from the package, in body order
+    pub bodies: Vec<(usize, Cow<'a, [u8]>)>,
+    /// The number of zero bytes after the last body
+    pub tail: usize,
+    /// Whether a SHA-1 digest of the body follows the body
+    pub checksum: bool,
+}
 
-    // Write filenames with null terminators
-    for filename in filenames {
-        bytes_data.extend_from_slice(filename.as_bytes());
-        bytes_data.push(0); // Null terminator
+impl EncodedMix<'_> {
+    /// The SHA-1 digest of the body, if the archive is checksummed
+    pub fn digest(&self) -> Option<[u8; CHECKSUM_SIZE]> {
+        if !self.checksum {
+            return None;
+        }
+        let mut hasher = Sha1::new();
+        for (gap, data) in &self.bodies {
+            hasher.update(vec![0; *gap]);
+            hasher.update(data);
+        }
+        hasher.update(vec![0; self.tail]);
+        Some(hasher.finalize().into())
     }
+}
 
-    bytes_data
+/// Checks that the file count and body size fit in the MIX header
+fn check_limits(file_information_list: &[FileInfo], limits: VolumeLimits) -> Result<(), MixError> {
+    let max_files = limits.max_files.min(MAX_FILE_COUNT);
+    if file_information_list.len() > max_files {
+        return Err(MixError::TooManyFiles { count: file_information_list.len(), limit: max_files });
+    }
+    let max_size = limits.max_size.min(MAX_BODY_SIZE);
+    let size = file_information_list.iter().map(|file_info| file_info.data.len() as u64).sum::<u64>();
+    if size > max_size {
+        return Err(MixError::BodyTooLarge { size, limit: max_size });
+    }
+    Ok(())
 }
 
-/// Processes input files and creates a file map
-pub fn coalesce_input_files(game: XccGame, file_map: &HashMap<String, Vec<u8>>) -> Result<HashMap<String, Vec<u8>>, MixError> {
-    let mut extra_file_map = file_map.clone();
-    // Get filenames and create mix database
-    let mut filenames: Vec<String> = extra_file_map.keys().cloned().collect();
-    filenames.push(MIX_DB_FILENAME.to_string());
-    // Sort filenames
-    let db_data = get_mix_db_data(&filenames, game);
-    extra_file_map.insert(MIX_DB_FILENAME.to_string(), db_data);
-    Ok(extra_file_map)
+/// Processes input files and creates the file list, with the local mix database written according to the package policy
+///
+/// Unnamed entries are appended unless a named file already has the same ID.
+fn coalesce_input_files(package: &MixPackage) -> Result<Vec<FileInfo<'_>>, MixError> {
+    let mix_db_id = ra2_crc(MIX_DB_FILENAME);
+    // The database is always rebuilt from the policy, never copied from the files
+    let mut filenames: Vec<&String> = package.files.keys().filter(|filename| ra2_crc(filename) != mix_db_id).collect();
+    filenames.sort();
+
+    // Reject names that share an ID
+    let mut file_information_list = Vec::with_capacity(filenames.len() + package.unnamed.len() + 1);
+    let mut known_ids: HashMap<i32, &String> = HashMap::with_capacity(filenames.len());
+    let file_ids = par_map(&filenames, |filename| ra2_crc(filename));
+    for (filename, file_id) in filenames.iter().zip(file_ids) {
+        if let Some(first) = known_ids.insert(file_id, filename) {
+            return Err(MixError::NameCollision { id: file_id, first: first.clone(), second: filename.to_string() });
+        }
+        let name = Some(filename.to_string());
+        file_information_list.push(FileInfo { file_id, name, data: Cow::Borrowed(&package.files[*filename]) });
+    }
+    let mut unnamed: Vec<(&i32, &Vec<u8>)> = package.unnamed.iter().collect();
+    unnamed.sort_by_key(|(file_id, _)| **file_id);
+    for (file_id, data) in unnamed {
+        if *file_id != mix_db_id && !known_ids.contains_key(file_id) {
+            file_information_list.push(FileInfo { file_id: *file_id, name: None, data: Cow::Borrowed(data) });
+        }
+    }
+
+    let filenames: Vec<String> = filenames.into_iter().cloned().collect();
+    let database = match (package.database_policy, &package.database) {
+        (DatabasePolicy::Omit, _) => None,
+        (DatabasePolicy::Preserve, Some(original)) => {
+            let known: HashSet<&str> = original.names.iter().map(String::as_str).collect();
+            if let Some(missing) = filenames.iter().find(|name| !known.contains(name.as_str())) {
+                return Err(MixError::Unsupported(format!(
+                    "The preserved local mix database does not name `{}`, merge it instead",
+                    missing
+                )));
+            }
+            Some(original.clone())
+        }
+        (DatabasePolicy::Merge, Some(original)) => {
+            // Like a regenerated database, the names are sorted and the database names itself last
+            let names: BTreeSet<&str> = original.names.iter().chain(&filenames).map(String::as_str).collect();
+            let mut names: Vec<String> =
+                names.into_iter().filter(|name| *name != MIX_DB_FILENAME).map(str::to_string).collect();
+            names.push(MIX_DB_FILENAME.to_string());
+            Some(LocalMixDatabase { game: package.game, names })
+        }
+        _ => {
+            let mut names = filenames;
+            names.push(MIX_DB_FILENAME.to_string());
+            Some(LocalMixDatabase { game: package.game, names })
+        }
+    };
+    if let Some(database) = database {
+        let name = Some(MIX_DB_FILENAME.to_string());
+        file_information_list.push(FileInfo { file_id: mix_db_id, name, data: Cow::Owned(database.encode()) });
+    }
+    Ok(file_information_list)
 }
 
 /// Creates a MIX file header
-fn create_mix_header(file_map: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>, MixError> {
-    let flags = 0u32;
-    let file_count = file_map.len() as u16;
-    let data_size = file_map.values().map(|data| data.len() as u32).sum();
-
+fn create_mix_header(flags: u32, file_count: u16, data_size: u32) -> Result<Vec<u8>, MixError> {
     let mut header = Vec::with_capacity(HEADER_SIZE);
     header.write_u32::<LittleEndian>(flags)?;
     header.write_u16::<LittleEndian>(file_count)?;
@@ -1,4 +1,937 @@
+use ra2_mix::{
+    ArchiveSource, BodyLayout, CancellationToken, ChangeKind, ConflictPolicy, DatabasePolicy, DirectorySource, EntryOrder,
+    LineChange, MixDiff, MixError, MixIndex, MixPackage, MixReader, MixTask, Progress, ProgressStage, VolumeLimits, XccGame,
+    content_hash, extract, extract_path, extract_with, patch, patch_with, ra2_crc,
+};
+
 #[test]
 fn ready() {
     println!("it works!")
 }
+
+#[test]
+fn round_trip_keeps_game() {
+    let mut mix = MixPackage { game: XccGame::RA2_YR, ..Default::default() };
+    mix.add_any("hello.txt".to_string(), b"Hello, World!".to_vec());
+    let decoded = MixPackage::decode(&mix.encode().unwrap()).unwrap();
+    assert_eq!(decoded.game, XccGame::RA2_YR);
+    assert_eq!(decoded.files["hello.txt"], b"Hello, World!");
+}
+
+#[test]
+fn database_policies() {
+    let mut mix = MixPackage::default();
+    mix.add_any("b.txt".to_string(), b"b".to_vec());
+    mix.add_any("a.txt".to_string(), b"a".to_vec());
+    let decoded = MixPackage::decode(&mix.encode().unwrap()).unwrap();
+    let names = &decoded.database.as_ref().unwrap().names;
+    assert_eq!(names, &["a.txt", "b.txt", "local mix database.dat"]);
+
+    let mut merged = decoded;
+    merged.database.as_mut().unwrap().names.splice(0..0, ["third-party.ini".to_string(), "b.txt".to_string()]);
+    merged.database_policy = DatabasePolicy::Merge;
+    merged.add_any("c.txt".to_string(), b"c".to_vec());
+    let decoded = MixPackage::decode(&merged.encode().unwrap()).unwrap();
+    let names = &decoded.database.as_ref().unwrap().names;
+    assert_eq!(names, &["a.txt", "b.txt", "c.txt", "third-party.ini", "local mix database.dat"]);
+
+    let mut preserved = decoded.clone();
+    preserved.database_policy = DatabasePolicy::Preserve;
+    let database = MixPackage::decode(&preserved.clone().encode().unwrap()).unwrap().database;
+    assert_eq!(database, decoded.database, "written untouched");
+    preserved.add_any("d.txt".to_string(), b"d".to_vec());
+    assert!(matches!(preserved.encode().unwrap_err(), MixError::Unsupported(_)), "d.txt would lose its name");
+
+    let mut omitted = decoded;
+    omitted.database_policy = DatabasePolicy::Omit;
+    let decoded = MixPackage::decode(&omitted.encode().unwrap()).unwrap();
+    assert!(decoded.database.is_none());
+}
+
+#[test]
+fn name_collision() {
+    let mut mix = MixPackage::default();
+    mix.add_any("rules.ini".to_string(), vec![1]);
+    mix.add_any("RULES.INI".to_string(), vec![2]);
+    assert!(matches!(mix.encode(), Err(MixError::NameCollision { .. })));
+}
+
+#[test]
+fn too_many_files() {
+    let mut mix = MixPackage::default();
+    for i in 0..4 {
+        mix.add_any(format!("{}.bin", i), vec![0; 10]);
+    }
+    let limits = VolumeLimits { max_files: 4, max_size: 1000 };
+    assert!(matches!(mix.check_limits(limits), Err(MixError::TooManyFiles { count: 5, limit: 4 })), "with the database");
+    let limits = VolumeLimits { max_files: 5, max_size: 40 };
+    assert!(matches!(mix.check_limits(limits), Err(MixError::BodyTooLarge { limit: 40, .. })));
+    assert!(mix.check_limits(VolumeLimits::default()).is_ok());
+
+    // Offsets are signed in the index, a body that ends past `i32::MAX` could not be read back
+    let mut mix = MixPackage { database_policy: DatabasePolicy::Omit, ..Default::default() };
+    mix.add_any("a.bin".to_string(), vec![0]);
+    mix.layout = BodyLayout { padding: i32::MAX as u32, ..Default::default() };
+    let limit = i32::MAX as u64;
+    assert!(matches!(mix.encode(), Err(MixError::BodyTooLarge { size, limit: max }) if size == limit + 1 && max == limit));
+    assert_eq!(VolumeLimits::default().max_size, limit);
+}
+
+#[test]
+fn split_volumes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut mix = MixPackage::default();
+    for i in 0..10 {
+        mix.add_any(format!("file{}.txt", i), vec![i; 100]);
+    }
+    mix.unnamed.insert(0x1234, vec![0xFF; 100]);
+    let limits = VolumeLimits { max_files: 4, max_size: 1000 };
+    let paths = mix.save_volumes(dir.path(), "expandmd", 1, limits).unwrap();
+    assert_eq!(paths.len(), 4, "three entries and a database per volume");
+    assert_eq!(paths[0], dir.path().join("expandmd01.mix"));
+
+    let mut merged = MixPackage::default();
+    for path in &paths {
+        let index = MixIndex::parse(&std::fs::read(path).unwrap()).unwrap();
+        assert!(index.header.file_count <= 4 && u64::from(index.header.data_size) <= 1000);
+        let volume = MixPackage::load(path).unwrap();
+        assert!(volume.check_limits(limits).is_ok());
+        merged.files.extend(volume.files);
+        merged.unnamed.extend(volume.unnamed);
+    }
+    assert_eq!(merged.files.len(), 10);
+    assert_eq!(merged.files["file7.txt"], vec![7; 100]);
+    assert_eq!(merged.unnamed[&0x1234], vec![0xFF; 100]);
+}
+
+#[test]
+fn merge_by_id() {
+    let mut named = MixPackage::default();
+    named.add_any("art.ini".to_string(), b"art".to_vec());
+    let mut unnamed = MixPackage::default();
+    unnamed.unnamed.insert(ra2_crc("art.ini"), b"art".to_vec());
+    unnamed.unnamed.insert(0x1234, b"other".to_vec());
+
+    let (merged, report) = MixPackage::merge(vec![unnamed, named], ConflictPolicy::Error).unwrap();
+    assert_eq!(merged.files["art.ini"], b"art");
+    assert_eq!(merged.unnamed[&0x1234], b"other");
+    assert_eq!(report.conflicts.len(), 1);
+    assert!(report.conflicts[0].identical);
+}
+
+#[test]
+fn diff_and_patch() {
+    let mut old = MixPackage::default();
+    old.add_any("rules.ini".to_string(), b"[General]\nA=1\nB=2\nC=3\n".to_vec());
+    old.add_any("gone.shp".to_string(), vec![1, 2, 3]);
+    old.add_any("same.shp".to_string(), vec![4]);
+    let mut new = MixPackage::default();
+    new.add_any("rules.ini".to_string(), b"[General]\nA=1\nB=5\nC=3\nD=4\n".to_vec());
+    new.add_any("new.shp".to_string(), vec![9; 10]);
+    new.add_any("same.shp".to_string(), vec![4]);
+
+    let diff = MixDiff::compare(&old, &new);
+    assert_eq!(diff.changes.len(), 3);
+    let rules = diff.changes.iter().find(|change| change.name.as_deref() == Some("rules.ini")).unwrap();
+    assert_eq!(rules.kind, ChangeKind::Modified);
+    assert_eq!(rules.size_delta(), 4);
+    assert_eq!(
+        rules.lines.as_deref().unwrap(),
+        &[
+            LineChange::Removed { line: 3, text: "B=2".to_string() },
+            LineChange::Added { line: 3, text: "B=5".to_string() },
+            LineChange::Added { line: 5, text: "D=4".to_string() },
+        ]
+    );
+
+    let patch = diff.patch(&new);
+    let mut names: Vec<_> = patch.files.keys().cloned().collect();
+    names.sort();
+    assert_eq!(names, ["new.shp", "rules.ini"]);
+}
+
+#[test]
+fn diff_large_texts() {
+    // Rebuilds the new text from the old one and the changes
+    let apply = |old: &str, changes: &[LineChange]| -> String {
+        let mut removed = std::collections::HashSet::new();
+        let mut added = Vec::new();
+        for change in changes {
+            match change {
+                LineChange::Removed { line, .. } => {
+                    removed.insert(*line);
+                }
+                LineChange::Added { line, text } => added.push((*line, text.as_str())),
+            }
+        }
+        added.reverse();
+        let mut kept = old.lines().enumerate().filter(|(i, _)| !removed.contains(&(i + 1))).map(|(_, text)| text);
+        let mut lines = Vec::new();
+        loop {
+            match added.last() {
+                Some((line, text)) if *line == lines.len() + 1 => {
+                    lines.push(*text);
+                    added.pop();
+                }
+                _ => match kept.next() {
+                    Some(text) => lines.push(text),
+                    None => break,
+                },
+            }
+        }
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    };
+    let compare = |old: &str, new: &str| {
+        let mut old_mix = MixPackage::default();
+        old_mix.add_any("rulesmd.ini".to_string(), old.as_bytes().to_vec());
+        let mut new_mix = MixPackage::default();
+        new_mix.add_any("rulesmd.ini".to_string(), new.as_bytes().to_vec());
+        let diff = MixDiff::compare(&old_mix, &new_mix);
+        let changes = diff.changes[0].lines.clone().unwrap();
+        assert_eq!(apply(old, &changes), new);
+        changes
+    };
+
+    // Scattered edits, insertions and removals
+    let old: String = (0..3000).map(|i| format!("Key{}={}\n", i, i % 7)).collect();
+    let new: String = (0..3000)
+        .filter(|i| i % 11 != 0)
+        .flat_map(|i| match i % 13 {
+            0 => vec![format!("Key{}=changed\n", i), format!("Extra{}=1\n", i)],
+            _ => vec![format!("Key{}={}\n", i, i % 7)],
+        })
+        .collect();
+    let changes = compare(&old, &new);
+    let removed = changes.iter().filter(|change| matches!(change, LineChange::Removed { .. })).count();
+    assert_eq!(removed, 273 + 231 - 21, "every 11th line and the 13th lines that are not 11th");
+
+    // A fully rewritten file of a typical size stays fast and small
+    let old: String = (0..20000).map(|i| format!("Old{}=1\n", i)).collect();
+    let new: String = (0..20000).map(|i| format!("New{}=2\n", i)).collect();
+    assert_eq!(compare(&old, &new).len(), 40000);
+}
+
+#[test]
+fn truncated_archive() {
+    let mut mix = MixPackage::default();
+    mix.add_any("a.txt".to_string(), b"a".to_vec());
+    mix.add_any("b.txt".to_string(), b"b".to_vec());
+    let data = mix.encode().unwrap();
+    let error = MixPackage::decode(&data[..20]).unwrap_err();
+    assert!(matches!(error, MixError::Truncated { offset: 10, needed: 36, available: 10 }));
+    let boxed: Box<dyn std::error::Error> = Box::new(error.context("Failed to load `a.mix`"));
+    assert!(boxed.source().is_some());
+}
+
+#[test]
+fn missing_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("plain.mix");
+    let mut mix = MixPackage { database_policy: DatabasePolicy::Omit, ..Default::default() };
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    mix.save(&path).unwrap();
+
+    let (package, warnings) = MixPackage::load_with_warnings(&path).unwrap();
+    assert_eq!(warnings.iter().map(|warning| warning.code()).collect::<Vec<_>>(), ["missing-database"]);
+    assert!(package.database.is_none());
+    assert_eq!(package.unnamed[&ra2_crc("rulesmd.ini")], b"[General]");
+}
+
+#[test]
+fn malformed_database() {
+    use ra2_mix::Catalog;
+
+    let mut mix = MixPackage::default();
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    let mut data = mix.encode().unwrap();
+    let index = MixIndex::parse(&data).unwrap();
+    let database = index.entry(ra2_crc("local mix database.dat")).unwrap();
+    // Breaks the XCC signature
+    data[index.body_offset as usize + database.offset as usize] ^= 0xFF;
+
+    let (package, warnings) = MixPackage::decode_with_warnings(&data).unwrap();
+    assert_eq!(warnings.iter().map(|warning| warning.code()).collect::<Vec<_>>(), ["invalid-database"]);
+    assert_eq!((package.files.len(), package.unnamed.len()), (0, 1));
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("odd.mix"), &data).unwrap();
+    let catalog = Catalog::build(dir.path()).unwrap();
+    assert!(catalog.archives()[0].error.is_none());
+    assert_eq!(catalog.lookup_id(ra2_crc("rulesmd.ini")).len(), 1);
+}
+
+#[test]
+fn database_outside_body() {
+    let mut mix = MixPackage::default();
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    let mut data = mix.encode().unwrap();
+    let index = MixIndex::parse(&data).unwrap();
+    let database = index.entry(ra2_crc("local mix database.dat")).unwrap();
+    // Cuts the body before the database, as protected archives do
+    data.truncate(index.body_offset as usize + database.offset as usize);
+
+    let (package, warnings) = MixPackage::decode_with_warnings(&data).unwrap();
+    let codes: Vec<&str> = warnings.iter().map(|warning| warning.code()).collect();
+    assert_eq!(codes, ["entry-out-of-bounds", "missing-database"]);
+    assert_eq!(package.unnamed[&ra2_crc("rulesmd.ini")], b"[General]");
+
+    let reader = MixReader::new(std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(reader.warnings(), warnings);
+}
+
+#[test]
+fn extract_hostile_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut mix = MixPackage::default();
+    for name in ["../escape.txt", "/tmp/absolute.txt", "nested\\file.txt", "..", "rules.ini"] {
+        mix.add_any(name.to_string(), name.as_bytes().to_vec());
+    }
+    let input = dir.path().join("hostile.mix");
+    mix.save(&input).unwrap();
+
+    let output = dir.path().join("out");
+    extract(&input, &output).unwrap();
+    assert!(!dir.path().join("escape.txt").exists());
+    let mut written: Vec<String> =
+        std::fs::read_dir(&output).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    written.sort();
+    let mut expected: Vec<String> = ["../escape.txt", "/tmp/absolute.txt", "nested\\file.txt", ".."]
+        .iter()
+        .map(|name| format!("{:08X}", ra2_crc(name)))
+        .chain(["rules.ini".to_string()])
+        .collect();
+    expected.sort();
+    assert_eq!(written, expected);
+    let path = extract_path(&output, "../escape.txt", ra2_crc("../escape.txt"));
+    assert_eq!(std::fs::read(path).unwrap(), b"../escape.txt");
+}
+
+#[test]
+fn patch_keeps_existing_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("mod");
+    std::fs::create_dir_all(folder.join("nested")).unwrap();
+    std::fs::write(folder.join("rulesmd.ini"), b"[Patched]").unwrap();
+    std::fs::write(folder.join("new.shp"), b"shp").unwrap();
+    std::fs::write(folder.join("nested").join("skipped.txt"), b"skipped").unwrap();
+
+    let output = dir.path().join("expandmd01.mix");
+    let mut mix = MixPackage::default();
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    mix.add_any("art.ini".to_string(), b"[Art]".to_vec());
+    mix.save(&output).unwrap();
+
+    patch(&folder, &output).unwrap();
+    let patched = MixPackage::load(&output).unwrap();
+    let mut names: Vec<&str> = patched.files.keys().map(|name| name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["art.ini", "new.shp", "rulesmd.ini"]);
+    assert_eq!(patched.files["art.ini"], b"[Art]");
+    assert_eq!(patched.files["rulesmd.ini"], b"[Patched]");
+
+    let created = dir.path().join("created.mix");
+    patch(&folder, &created).unwrap();
+    assert_eq!(MixPackage::load(&created).unwrap().files.len(), 2);
+}
+
+#[test]
+fn extract_and_patch_with_progress() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut mix = MixPackage::default();
+    mix.add_any("a.txt".to_string(), b"aaaa".to_vec());
+    mix.add_any("b.txt".to_string(), b"bb".to_vec());
+    let input = dir.path().join("input.mix");
+    mix.clone().save(&input).unwrap();
+
+    // Encoding reports the bodies as they are copied, the last event is the end of the work
+    let mut events = Vec::new();
+    let mut observer = |progress: &Progress| events.push(*progress);
+    mix.clone().encode_with(&mut MixTask::new().with_observer(&mut observer)).unwrap();
+    assert_eq!(events.len(), 3, "the files and the local mix database");
+    assert!(events.windows(2).all(|pair| pair[0].bytes < pair[1].bytes));
+    assert_eq!(events[2].bytes, events[2].total_bytes);
+
+    let mut events = Vec::new();
+    let mut observer = |progress: &Progress| events.push(*progress);
+    let mut task = MixTask::new().with_observer(&mut observer);
+    extract_with(&input, &dir.path().join("out"), &mut task).unwrap();
+    let last = events.iter().rfind(|progress| progress.stage == ProgressStage::Extract).unwrap();
+    assert_eq!((last.entry, last.entries, last.bytes, last.total_bytes), (2, 2, 6, 6));
+
+    let token = CancellationToken::new();
+    let mut cancel_after_first = |_: &Progress| token.cancel();
+    let mut task = MixTask::new().with_observer(&mut cancel_after_first).with_cancellation(token.clone());
+    let output = dir.path().join("patched.mix");
+    assert!(matches!(patch_with(&dir.path().join("out"), &output, &mut task), Err(MixError::Cancelled)));
+    assert!(!output.exists());
+    let mut task = MixTask::new().with_cancellation(token.clone());
+    assert!(matches!(MixPackage::load_with(&input, &mut task), Err(MixError::Cancelled)));
+
+    patch(&dir.path().join("out"), &output).unwrap();
+    assert_eq!(MixPackage::load(&output).unwrap().files.len(), 2);
+}
+
+#[test]
+fn parallel_encoding_matches_serial() {
+    let package = || {
+        let mut mix = MixPackage { checksum: true, ..Default::default() };
+        mix.layout.alignment = 16;
+        mix.layout.padding = 3;
+        for i in 0..200 {
+            mix.add_any(format!("unit{:03}.shp", i), vec![i as u8; i]);
+        }
+        mix
+    };
+    let parallel = package().encode().unwrap();
+    // A single thread runs every parallel step in input order
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let serial = pool.install(|| package().encode().unwrap());
+    assert_eq!(parallel, serial);
+    assert_eq!(MixPackage::decode(&parallel).unwrap().files["unit199.shp"], vec![199; 199]);
+}
+
+#[test]
+fn reproducible_encoding() {
+    let package = |reverse: bool| {
+        let mut names: Vec<String> = (0..50).map(|i| format!("file{:02}.txt", i)).collect();
+        if reverse {
+            names.reverse();
+        }
+        let mut mix = MixPackage::default();
+        for name in names {
+            mix.add_any(name.clone(), name.into_bytes());
+        }
+        for id in [3, 1, 2] {
+            mix.unnamed.insert(id, vec![id as u8; 4]);
+        }
+        mix
+    };
+    let first = package(false).encode().unwrap();
+    assert_eq!(first, package(true).encode().unwrap());
+    assert_eq!(first, package(false).encode().unwrap());
+
+    // Re-encoding a decoded archive with every database policy is stable as well
+    for policy in [DatabasePolicy::Regenerate, DatabasePolicy::Preserve, DatabasePolicy::Merge] {
+        let mut decoded = MixPackage::decode(&first).unwrap();
+        decoded.database_policy = policy;
+        assert_eq!(decoded.encode().unwrap(), first);
+    }
+}
+
+#[test]
+fn body_layout() {
+    let package = |layout: BodyLayout| {
+        let mut mix = MixPackage { layout, database_policy: DatabasePolicy::Omit, ..Default::default() };
+        mix.add_any("a.shp".to_string(), vec![1; 5]);
+        mix.add_any("b.shp".to_string(), vec![2; 7]);
+        mix.add_any("c.shp".to_string(), vec![3; 3]);
+        mix
+    };
+    let order = EntryOrder::Priority(vec!["c.shp".to_string(), "a.shp".to_string()]);
+    let data = package(BodyLayout { alignment: 8, order, padding: 4 }).encode().unwrap();
+    let index = MixIndex::parse(&data).unwrap();
+    assert!(index.entries.windows(2).all(|pair| pair[0].id < pair[1].id));
+    let offset = |name: &str| index.entry(ra2_crc(name)).unwrap().offset;
+    assert_eq!((offset("c.shp"), offset("a.shp"), offset("b.shp")), (0, 8, 24));
+
+    // Decoding ignores the gaps, and the original offsets can be kept on re-encoding
+    let mut decoded = MixPackage::decode(&data).unwrap();
+    assert_eq!(decoded.unnamed[&ra2_crc("b.shp")], vec![2; 7]);
+    decoded.database_policy = DatabasePolicy::Omit;
+    decoded.layout = BodyLayout { alignment: 8, order: EntryOrder::OriginalOffset, padding: 4 };
+    assert_eq!(decoded.encode().unwrap(), data);
+}
+
+#[test]
+fn entry_streams() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut mix = MixPackage::default();
+    mix.add_any("first.bin".to_string(), (0..100).collect());
+    mix.add_any("second.bin".to_string(), (100..150).collect());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stream.mix");
+    mix.save(&path).unwrap();
+
+    let mut reader = MixReader::open(&path).unwrap();
+    assert!(reader.warnings().is_empty());
+    let mut entry = reader.entry_by_name("SECOND.BIN").unwrap();
+    assert_eq!(entry.len(), 50);
+    let mut buffer = [0; 4];
+    entry.seek(SeekFrom::Start(10)).unwrap();
+    entry.read_exact(&mut buffer).unwrap();
+    assert_eq!(buffer, [110, 111, 112, 113]);
+    assert_eq!(entry.seek(SeekFrom::Current(-2)).unwrap(), 12);
+    assert_eq!(entry.seek(SeekFrom::End(-1)).unwrap(), 49);
+    let mut rest = Vec::new();
+    entry.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, [149]);
+    assert!(entry.seek(SeekFrom::Current(-100)).is_err());
+
+    assert_eq!(reader.read_entry(ra2_crc("first.bin")).unwrap(), (0..100).collect::<Vec<u8>>());
+    assert!(reader.entry_by_name("missing.bin").is_err());
+}
+
+#[test]
+fn archive_sources() {
+    use std::io::Read;
+
+    fn read_rules(source: &mut dyn ArchiveSource) -> String {
+        let mut text = String::new();
+        source.open("RULES.INI").unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let folder = dir.path().join("mod");
+    std::fs::create_dir(&folder).unwrap();
+    std::fs::write(folder.join("Rules.ini"), "[General]").unwrap();
+    std::fs::write(folder.join("unit.shp"), [1, 2, 3]).unwrap();
+    patch(&folder, &dir.path().join("mod.mix")).unwrap();
+
+    let mut directory = DirectorySource::new(&folder).unwrap();
+    let mut package = MixPackage::load(&dir.path().join("mod.mix")).unwrap();
+    let mut reader = MixReader::open(&dir.path().join("mod.mix")).unwrap();
+    let sources: [&mut dyn ArchiveSource; 3] = [&mut directory, &mut package, &mut reader];
+    for source in sources {
+        assert_eq!(read_rules(source), "[General]");
+        assert!(source.contains("UNIT.SHP"));
+        assert!(!source.contains("missing.shp"));
+        assert_eq!(source.metadata("unit.shp").unwrap().size, 3);
+        assert!(matches!(source.open("missing.shp"), Err(MixError::FileNotFound(_))));
+        let names: Vec<String> = source.list().into_iter().filter_map(|entry| entry.name).collect();
+        assert!(names.iter().any(|name| name == "Rules.ini"));
+    }
+
+    // The engine cannot tell apart names that differ only in case
+    std::fs::write(folder.join("RULES.INI"), "[Other]").unwrap();
+    match DirectorySource::new(&folder) {
+        Err(MixError::NameCollision { id, first, second }) => {
+            assert_eq!((id, first.as_str(), second.as_str()), (ra2_crc("rules.ini"), "RULES.INI", "Rules.ini"))
+        }
+        other => panic!("expected a name collision, got {:?}", other.map(|source| source.list())),
+    }
+}
+
+/// Writes a game folder with an expansion archive holding a nested archive, the main archive and an invalid archive
+///
+/// Returns the folder and the main archive, to change it later
+fn game_folder(root: &std::path::Path) -> (std::path::PathBuf, MixPackage) {
+    let game = root.join("game");
+    std::fs::create_dir_all(game.join("MIXES")).unwrap();
+    let mut maps = MixPackage::default();
+    maps.add_any("tank.map".to_string(), b"[Basic]\nName=Tank Rush".to_vec());
+    let mut expand = MixPackage::default();
+    expand.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    expand.add_any("GAPOWR.SHP".to_string(), vec![1, 2, 3, 4]);
+    expand.add_any("maps.mix".to_string(), maps.encode().unwrap());
+    expand.save(&game.join("MIXES/expandmd01.mix")).unwrap();
+    let mut ra2 = MixPackage::default();
+    ra2.add_any("gapowr.shp".to_string(), vec![1, 2, 3]);
+    ra2.clone().save(&game.join("ra2md.mix")).unwrap();
+    std::fs::write(game.join("broken.mix"), [1, 2]).unwrap();
+    (game, ra2)
+}
+
+#[test]
+fn game_catalog() {
+    use ra2_mix::Catalog;
+
+    let dir = tempfile::tempdir().unwrap();
+    let (game, mut ra2) = game_folder(dir.path());
+    // A link back to the game folder is not followed
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&game, game.join("MIXES/game")).unwrap();
+
+    let cache = dir.path().join("game.catalog");
+    let (catalog, update) = Catalog::open(&game, &cache).unwrap();
+    assert_eq!((update.added, update.unchanged, update.unreadable), (3, 0, 0));
+    assert!(catalog.archives().iter().any(|archive| archive.error.is_some()));
+
+    let hits = catalog.lookup("TANK.MAP");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].chain(), ["MIXES/expandmd01.mix", "maps.mix"]);
+    let data = std::fs::read(game.join("MIXES/expandmd01.mix")).unwrap();
+    let offset = hits[0].entry.offset as usize;
+    assert_eq!(&data[offset..offset + hits[0].entry.size as usize], b"[Basic]\nName=Tank Rush");
+    assert_eq!(catalog.lookup_hash(content_hash(&[1, 2, 3]))[0].entry.name.as_deref(), Some("gapowr.shp"));
+    assert!(catalog.lookup("missing.shp").is_empty());
+
+    ra2.add_any("gtpowr.shp".to_string(), vec![4]);
+    ra2.save(&game.join("ra2md.mix")).unwrap();
+    std::fs::remove_file(game.join("broken.mix")).unwrap();
+    let (catalog, update) = Catalog::open(&game, &cache).unwrap();
+    assert_eq!((update.added, update.changed, update.removed, update.unchanged), (0, 1, 1, 1));
+    assert_eq!(catalog.lookup("gtpowr.shp").len(), 1);
+    assert_eq!(Catalog::load(&cache).unwrap().entries().count(), catalog.entries().count());
+}
+
+#[test]
+fn search_archives() {
+    use ra2_mix::{Catalog, SearchQuery, search};
+
+    let dir = tempfile::tempdir().unwrap();
+    let (game, _) = game_folder(dir.path());
+
+    let inputs = [game.clone()];
+    let chains = |query: &SearchQuery| -> Vec<Vec<String>> {
+        search(&inputs, query).unwrap().iter().map(|hit| hit.chain()).collect()
+    };
+    let gapowr = SearchQuery::Glob("ga*.shp".to_string());
+    let archive = |name: &str| vec![game.join(name).to_string_lossy().replace('\\', "/")];
+    assert_eq!(chains(&gapowr), [archive("MIXES/expandmd01.mix"), archive("ra2md.mix")]);
+    let tank = chains(&SearchQuery::Bytes(b"Tank Rush".to_vec()));
+    assert_eq!(tank.len(), 2, "the map and the nested archive that holds it");
+    assert_eq!(tank[1][1..], ["maps.mix"]);
+    assert_eq!(chains(&SearchQuery::Id(ra2_crc("tank.map"))).len(), 1);
+    let hits = search(&inputs, &SearchQuery::Hash(content_hash(&[1, 2, 3]))).unwrap();
+    assert_eq!(hits.iter().map(|hit| hit.entry.hash).collect::<Vec<_>>(), [content_hash(&[1, 2, 3])]);
+    assert!(chains(&SearchQuery::Glob("*.vxl".to_string())).is_empty());
+    assert!(search(&[game.join("broken.mix")], &gapowr).is_err());
+
+    let catalog = Catalog::build(&game).unwrap();
+    let hits = catalog.search(&SearchQuery::Bytes(b"Tank".to_vec())).unwrap();
+    assert_eq!(hits.iter().filter_map(|hit| hit.entry.name.as_deref()).collect::<Vec<_>>(), ["maps.mix", "tank.map"]);
+    assert_eq!(catalog.search(&gapowr).unwrap().len(), 2);
+}
+
+#[test]
+fn shadowed_entries() {
+    use ra2_mix::{Catalog, ShadowReport};
+
+    let dir = tempfile::tempdir().unwrap();
+    let save = |name: &str, files: &[(&str, &[u8])]| {
+        let mut package = MixPackage::default();
+        for (file, data) in files {
+            package.add_any(file.to_string(), data.to_vec());
+        }
+        package.save(&dir.path().join(name)).unwrap();
+    };
+    save("ra2md.mix", &[("rulesmd.ini", b"[A]"), ("gapowr.shp", &[1, 2, 3]), ("gtpowr.shp", &[4])]);
+    save("expandmd01.mix", &[("rulesmd.ini", b"[B]"), ("gapowr.shp", &[1, 2, 3])]);
+    save("expandmd02.mix", &[("RULESMD.INI", b"[C]")]);
+    save("custom.mix", &[("gapowr.shp", &[1, 2, 3])]);
+
+    let report = ShadowReport::new(&Catalog::build(dir.path()).unwrap());
+    assert_eq!(report.groups.len(), 2, "only duplicated files, without the local mix databases");
+    let rules = report.groups.iter().find(|group| group.id == ra2_crc("rulesmd.ini")).unwrap();
+    assert_eq!(rules.winner.chain, ["expandmd02.mix"]);
+    let shadowed: Vec<_> = rules.shadowed.iter().map(|copy| (copy.chain[0].as_str(), copy.identical)).collect();
+    assert_eq!(shadowed, [("expandmd01.mix", false), ("ra2md.mix", false)]);
+    let gapowr = report.groups.iter().find(|group| group.id == ra2_crc("gapowr.shp")).unwrap();
+    assert_eq!(gapowr.name.as_deref(), Some("gapowr.shp"));
+    assert_eq!(gapowr.winner.chain, ["expandmd01.mix"]);
+    let shadowed: Vec<_> = gapowr.shadowed.iter().map(|copy| (copy.chain[0].as_str(), copy.identical)).collect();
+    assert_eq!(shadowed, [("ra2md.mix", true)]);
+    assert_eq!(gapowr.not_loaded.iter().map(|copy| copy.chain.clone()).collect::<Vec<_>>(), [["custom.mix"]]);
+    assert_eq!((report.identical, report.different, report.wasted), (1, 2, 3), "without the archive the game ignores");
+}
+
+#[test]
+fn entry_kinds() {
+    use ra2_mix::EntryKind;
+
+    let mut nested = MixPackage::default();
+    nested.add_any("big.shp".to_string(), vec![0; 10_000]);
+    let nested = nested.encode().unwrap();
+    let mut checksummed = MixPackage { checksum: true, ..Default::default() };
+    checksummed.add_any("a.txt".to_string(), b"a".to_vec());
+    let checksummed = checksummed.encode().unwrap();
+
+    let cases: [(Option<&str>, &[u8], EntryKind); 14] = [
+        (Some("UNITS.SHP"), b"", EntryKind::Shp),
+        (Some("expandmd01.mix"), b"not an archive", EntryKind::Mix),
+        (Some("local mix database.dat"), b"XCC by Olaf van der Spek\x1a\x04\x17\x27\x10\x19\x80", EntryKind::Database),
+        // Unknown extensions and `.dat` files that are not databases fall back to the contents
+        (Some("data.dat"), b"[General]", EntryKind::Ini),
+        (Some("unit.bin"), &nested, EntryKind::Mix),
+        (None, &nested, EntryKind::Mix),
+        (None, &checksummed, EntryKind::Mix),
+        // A prefix of an archive does not match its header
+        (None, &nested[..4096], EntryKind::Unknown),
+        (None, b" FSC\x03\x00\x00\x00", EntryKind::Csf),
+        (None, b"RIFF\x24\x00\x00\x00WAVE", EntryKind::Wav),
+        (None, b"BIKi", EntryKind::Bik),
+        (None, b"\r\n[General]\r\nName=Test", EntryKind::Ini),
+        (None, b"Hello, World!", EntryKind::Text),
+        (None, b"\x00\x01\x02", EntryKind::Unknown),
+    ];
+    for (name, data, kind) in cases {
+        assert_eq!(EntryKind::detect(name, data), kind, "{:?}", name);
+    }
+    assert!(EntryKind::Map.is_text() && !EntryKind::Mix.is_text());
+}
+
+#[test]
+fn listing_export() {
+    use ra2_mix::{EntryKind, MixListing};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut mix = MixPackage { game: XccGame::RA2_YR, ..Default::default() };
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    mix.add_any("odd, \"name\".txt".to_string(), b"text".to_vec());
+    mix.unnamed.insert(0x1234, vec![0; 16]);
+    mix.save(&dir.path().join("listed.mix")).unwrap();
+
+    let listing = MixListing::open(&dir.path().join("listed.mix")).unwrap();
+    assert_eq!(listing.game, XccGame::RA2_YR);
+    assert_eq!(listing.entries.len(), 4, "the entries and the local mix database");
+    let rules = listing.entries.iter().find(|entry| entry.name.as_deref() == Some("rulesmd.ini")).unwrap();
+    assert_eq!((rules.kind, rules.size, rules.hash), (EntryKind::Ini, 9, content_hash(b"[General]")));
+    let csv = listing.to_csv();
+    assert_eq!(csv.lines().count(), 5);
+    assert!(csv.contains(&format!("{:08X},rulesmd.ini,INI,{},9,{:08X}\n", rules.id, rules.offset, rules.hash)));
+    assert!(csv.contains(",\"odd, \"\"name\"\".txt\",TXT,"));
+    assert!(csv.contains("00001234,,"));
+
+    #[cfg(feature = "serde_json")]
+    {
+        let json: serde_json::Value = serde_json::from_str(&listing.to_json().unwrap()).unwrap();
+        assert_eq!(json["game"], "RA2_YR");
+        assert_eq!(json["header"]["file_count"], 4);
+        let entries = json["entries"].as_array().unwrap();
+        assert!(entries.iter().any(|entry| entry["kind"] == "ini" && entry["size"] == 9));
+        let (id, hash) = (format!("{:08X}", rules.id), format!("{:08X}", rules.hash));
+        assert!(entries.iter().any(|entry| entry["id"] == id && entry["hash"] == hash), "the same digits as the CSV");
+        assert!(entries.iter().any(|entry| entry["id"] == "00001234"));
+        assert_eq!(serde_json::from_value::<MixListing>(json).unwrap(), listing);
+    }
+}
+
+#[test]
+fn listing_protected() {
+    use ra2_mix::MixListing;
+
+    let mut mix = MixPackage::default();
+    mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+    let mut data = mix.encode().unwrap();
+    let index = MixIndex::parse(&data).unwrap();
+    let database = index.entry(ra2_crc("local mix database.dat")).unwrap();
+    data.truncate(index.body_offset as usize + database.offset as usize);
+
+    let mut reader = MixReader::new(std::io::Cursor::new(data)).unwrap();
+    let (listing, warnings) = MixListing::read_with_warnings(&mut reader).unwrap();
+    assert_eq!(listing.entries.len(), 2, "the entry outside the body is still listed");
+    let row = listing.entries.iter().find(|entry| entry.id == database.id).unwrap();
+    assert!(row.out_of_bounds);
+    assert_eq!((row.size, row.hash), (0, content_hash(b"")));
+    let rules = listing.entries.iter().find(|entry| entry.id == ra2_crc("rulesmd.ini")).unwrap();
+    assert!(!rules.out_of_bounds);
+    assert_eq!(rules.size, 9);
+    let codes: Vec<&str> = warnings.iter().map(|warning| warning.code()).collect();
+    assert_eq!(codes, ["entry-out-of-bounds", "missing-database"], "reported once");
+}
+
+#[cfg(feature = "manifest")]
+#[test]
+fn manifest_build() {
+    use ra2_mix::{Manifest, MixIndex};
+
+    let dir = tempfile::tempdir().unwrap();
+    // Brackets in the base folder are not a pattern
+    let base = dir.path().join("mod [v2]");
+    std::fs::create_dir_all(base.join("rules")).unwrap();
+    std::fs::create_dir_all(base.join("override")).unwrap();
+    std::fs::create_dir_all(base.join("maps")).unwrap();
+    std::fs::write(base.join("rules/rulesmd.ini"), "[General]").unwrap();
+    std::fs::write(base.join("rules/readme.txt"), "skip").unwrap();
+    std::fs::write(base.join("override/RULESMD.INI"), "[Override]").unwrap();
+    std::fs::write(base.join("maps/a.map"), "[Map]").unwrap();
+    let manifest = base.join("build.toml");
+    std::fs::write(
+        &manifest,
+        r#"
+        game = "RA2_YR"
+
+        [[archive]]
+        name = "expandmd01.mix"
+        sources = ["rules/*.ini", "override/*.INI"]
+        mixes = ["maps.mix"]
+        checksum = true
+
+        [[archive]]
+        name = "maps.mix"
+        sources = ["maps/*.map"]
+        database = "omit"
+        "#,
+    )
+    .unwrap();
+
+    let written = Manifest::load(&manifest).unwrap().build_to(&base, &dir.path().join("out")).unwrap();
+    assert_eq!(written, vec![dir.path().join("out/expandmd01.mix")]);
+    let data = std::fs::read(&written[0]).unwrap();
+    assert!(MixIndex::parse(&data).unwrap().header.has_checksum());
+    let package = MixPackage::decode(&data).unwrap();
+    assert_eq!(package.game, XccGame::RA2_YR);
+    assert!(package.checksum);
+    assert_eq!(package.files.len(), 2, "the later pattern replaces rulesmd.ini whatever its case");
+    assert_eq!(package.get_by_id(ra2_crc("rulesmd.ini")), Some(&b"[Override]"[..]));
+    let maps = MixPackage::decode(&package.files["maps.mix"]).unwrap();
+    assert_eq!(maps.unnamed[&ra2_crc("a.map")], b"[Map]");
+
+    let cyclic = Manifest::from_json(r#"{ "archive": [{ "name": "a.mix", "mixes": ["a.mix"] }] }"#).unwrap();
+    assert!(matches!(cyclic.build(dir.path()), Err(MixError::InvalidFormat(_))));
+    let encrypted = Manifest::from_json(r#"{ "archive": [{ "name": "a.mix", "encrypted": true }] }"#).unwrap();
+    assert!(matches!(encrypted.build(dir.path()).unwrap_err().root(), MixError::Unsupported(_)));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn watch_source_tree() {
+    use ra2_mix::{CancellationToken, MixWatcher};
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("src");
+    std::fs::create_dir_all(source.join("art")).unwrap();
+    std::fs::write(source.join("rulesmd.ini"), "[General]").unwrap();
+    std::fs::write(source.join("art/gapowr.shp"), [1, 2, 3]).unwrap();
+    std::fs::write(source.join(".rulesmd.ini.swp"), "swap").unwrap();
+    let target = source.join("expandmd01.mix");
+    let mut kept = MixPackage::default();
+    kept.add_any("keep.txt".to_string(), b"kept".to_vec());
+    kept.save(&target).unwrap();
+    // Links to folders, back into the tree or outside it, are not followed
+    #[cfg(unix)]
+    {
+        std::fs::create_dir(dir.path().join("outside")).unwrap();
+        std::fs::write(dir.path().join("outside/secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("outside"), source.join("outside")).unwrap();
+        std::os::unix::fs::symlink(&source, source.join("art/loop")).unwrap();
+    }
+
+    let mut watcher = MixWatcher::new(&source, &target).unwrap();
+    let summary = watcher.sync().unwrap();
+    assert_eq!(summary.added, ["gapowr.shp", "rulesmd.ini"]);
+    assert!(summary.written.is_some());
+    assert!(!source.join(".expandmd01.mix.tmp").exists(), "the temporary file is renamed over the archive");
+    let names = |package: &MixPackage| {
+        let mut names: Vec<String> = package.files.keys().cloned().collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(&MixPackage::load(&target).unwrap()), ["gapowr.shp", "keep.txt", "rulesmd.ini"]);
+    assert!(watcher.sync().unwrap().is_empty(), "nothing changed, the archive is not written");
+
+    std::fs::write(source.join("rulesmd.ini"), "[General]\nName=Test").unwrap();
+    std::fs::remove_dir_all(source.join("art")).unwrap();
+    let summary = watcher.update(&[source.join("rulesmd.ini"), source.join("art")]).unwrap();
+    assert_eq!(summary.to_string(), format!("~rulesmd.ini -gapowr.shp ({} bytes)", summary.written.unwrap()));
+
+    // Watch on a thread and wait for the rebuild after a new file
+    let token = CancellationToken::new();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let worker = {
+        let token = token.clone();
+        let mut watcher = watcher.with_debounce(Duration::from_millis(50));
+        std::thread::spawn(move || watcher.watch(&token, |rebuild| sender.send(rebuild.unwrap()).unwrap()))
+    };
+    assert!(receiver.recv_timeout(Duration::from_secs(10)).unwrap().is_empty(), "the first sync");
+    std::fs::write(source.join("gtpowr.shp"), [4, 5]).unwrap();
+    let summary = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(summary.added, ["gtpowr.shp"]);
+    token.cancel();
+    worker.join().unwrap().unwrap();
+    assert_eq!(names(&MixPackage::load(&target).unwrap()), ["gtpowr.shp", "keep.txt", "rulesmd.ini"]);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn command_line() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_ra2-mix")).current_dir(dir.path()).args(args).output().unwrap();
+    std::fs::write(dir.path().join("rules.ini"), "[General]").unwrap();
+    std::fs::write(dir.path().join("art.ini"), "[Art]").unwrap();
+
+    assert!(run(&["create", "out.mix", "rules.ini", "art.ini", "--game", "yr", "--checksum"]).status.success());
+    let listed = run(&["list", "out.mix", "--json"]);
+    let listed: serde_json::Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert_eq!(listed["entries"].as_array().unwrap().len(), 3);
+    assert!(run(&["remove", "out.mix", "ART.INI"]).status.success());
+    let info: serde_json::Value = serde_json::from_slice(&run(&["info", "--json", "out.mix"]).stdout).unwrap();
+    assert_eq!((info["entries"].as_u64(), info["game"].as_str()), (Some(2), Some("RA2_YR")));
+    assert_eq!(run(&["verify", "out.mix"]).status.code(), Some(0));
+    let exported = String::from_utf8(run(&["export", "out.mix", "--format", "csv"]).stdout).unwrap();
+    assert!(exported.starts_with("id,name,kind,offset,size,hash\n") && exported.contains(",rules.ini,INI,"));
+    let exported: serde_json::Value = serde_json::from_slice(&run(&["export", "out.mix"]).stdout).unwrap();
+    assert_eq!(exported["game"], "RA2_YR");
+    let found = run(&["search", "--json", "--regex", "^r.*s\\.ini$", "."]);
+    let found: serde_json::Value = serde_json::from_slice(&found.stdout).unwrap();
+    assert_eq!(found["matches"][0]["chain"], serde_json::json!(["./out.mix"]));
+    let found = run(&["search", "--text", "[General]", "out.mix"]);
+    assert!(String::from_utf8_lossy(&found.stdout).ends_with("out.mix > rules.ini\n"));
+    assert_eq!(run(&["search", "--name", "*.ini", "--id", "0x1", "out.mix"]).status.code(), Some(2));
+    assert!(run(&["create", "ra2md.mix", "rules.ini"]).status.success());
+    assert!(run(&["create", "expandmd01.mix", "rules.ini"]).status.success());
+    let duplicates: serde_json::Value = serde_json::from_slice(&run(&["duplicates", "--json", "."]).stdout).unwrap();
+    assert_eq!(duplicates["duplicates"][0]["winner"]["chain"], serde_json::json!(["expandmd01.mix"]));
+    assert_eq!(duplicates["duplicates"][0]["not_loaded"][0]["chain"], serde_json::json!(["out.mix"]));
+    assert_eq!((duplicates["identical"].as_u64(), duplicates["wasted"].as_u64()), (Some(1), Some(9)));
+
+    assert!(run(&["extract", "out.mix", "extracted", "rules.ini"]).status.success());
+    assert_eq!(std::fs::read(dir.path().join("extracted/rules.ini")).unwrap(), b"[General]");
+    assert_eq!(run(&["remove", "out.mix", "missing.ini"]).status.code(), Some(1));
+    let failed = run(&["remove", "--json", "out.mix", "missing.ini"]);
+    assert!(failed.stderr.is_empty());
+    assert!(serde_json::from_slice::<serde_json::Value>(&failed.stdout).unwrap()["error"].is_string());
+    assert_eq!(run(&["extract", "out.mix", "extracted", "0xZZ"]).status.code(), Some(2));
+    assert_eq!(run(&["list"]).status.code(), Some(2));
+
+    // Adding a file named in another case replaces the entry
+    std::fs::create_dir(dir.path().join("upper")).unwrap();
+    std::fs::write(dir.path().join("upper/RULES.INI"), "[Upper]").unwrap();
+    assert!(run(&["add", "ra2md.mix", "upper/RULES.INI"]).status.success());
+    let added = MixPackage::load(&dir.path().join("ra2md.mix")).unwrap();
+    assert_eq!((added.files.len(), added.get_by_id(ra2_crc("rules.ini"))), (1, Some(&b"[Upper]"[..])));
+
+    // Flipping a body byte breaks the checksum
+    let mut data = std::fs::read(dir.path().join("out.mix")).unwrap();
+    let body_offset = MixIndex::parse(&data).unwrap().body_offset as usize;
+    data[body_offset] ^= 0xFF;
+    std::fs::write(dir.path().join("out.mix"), data).unwrap();
+    let verified: serde_json::Value = serde_json::from_slice(&run(&["verify", "--json", "out.mix"]).stdout).unwrap();
+    assert_eq!(verified["problems"][0]["code"], "checksum-mismatch");
+    assert_eq!(run(&["verify", "out.mix"]).status.code(), Some(3));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_round_trip() {
+    let package = || {
+        let mut mix = MixPackage { game: XccGame::RA2_YR, ..Default::default() };
+        mix.add_any("rulesmd.ini".to_string(), b"[General]".to_vec());
+        mix.add_any("unit.shp".to_string(), vec![7; 1000]);
+        mix
+    };
+    let expected = package().encode().unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("async.mix");
+    assert_eq!(package().save_async(&path).await.unwrap(), expected.len());
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+    let (loaded, warnings) = MixPackage::load_async(&path).await.unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(loaded.game, XccGame::RA2_YR);
+    assert_eq!(loaded.files["unit.shp"], vec![7; 1000]);
+
+    let mut reader = ra2_mix::AsyncMixReader::open(std::io::Cursor::new(expected)).await.unwrap();
+    assert_eq!(reader.index().entries.len(), 3);
+    let mut entry = reader.entry_reader(ra2_crc("rulesmd.ini")).await.unwrap();
+    let mut text = String::new();
+    tokio::io::AsyncReadExt::read_to_string(&mut entry, &mut text).await.unwrap();
+    assert_eq!(text, "[General]");
+
+    // Two entries with the same ID, each is read from its own range and the later body wins as in a sync read
+    let mut data = vec![0, 0, 0, 0, 2, 0, 4, 0, 0, 0];
+    for (offset, size) in [(0u32, 2u32), (2, 2)] {
+        data.extend(0x1234i32.to_le_bytes().into_iter().chain(offset.to_le_bytes()).chain(size.to_le_bytes()));
+    }
+    data.extend(b"aabb");
+    let reader = ra2_mix::AsyncMixReader::open(std::io::Cursor::new(data.clone())).await.unwrap();
+    let (package, _) = reader.into_package().await.unwrap();
+    assert_eq!(package.unnamed[&0x1234], b"bb");
+    assert_eq!(MixPackage::decode(&data).unwrap().unnamed, package.unnamed);
+}